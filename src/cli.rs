@@ -1,7 +1,25 @@
-use crate::modules::strava;
+use crate::modules::config::{
+    self, Config, OutputConfig, OutputFormat, Policy, ServiceConfig, SyncConfig, Units,
+};
+use crate::modules::profile::{strava_session, withings_session, Credentials, Profile, Profiles};
+use crate::modules::provider::{sync_weight, sync_workouts, SinkKind, SourceKind};
+use crate::modules::strava::gear::DEFAULT_WARNING_RATIO;
+use crate::modules::strava::{
+    ActivityFilter, ActivityUpdate, GearStatus, GearThresholds, NewActivity, Resolution,
+    RouteFormat, StravaSession, StreamType, UploadOptions, UploadOutcome, Visibility,
+};
+use crate::modules::weight::{is_valid_smoothing, WeightPolicy};
+use crate::modules::withings::{get_day_before_timestamp, Window};
 use crate::utils::get_and_format_weight;
-use clap::{Parser, Subcommand, ValueEnum};
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use colored_json::to_colored_json_auto;
+use miette::IntoDiagnostic;
+use serde::Serialize;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None, arg_required_else_help = true)]
@@ -10,6 +28,14 @@ struct Cli {
     #[arg(short, long)]
     log: bool,
 
+    /// Profile whose credentials and tokens to use instead of the environment variables
+    #[arg(long, global = true, env = "FIT_CONNECT_PROFILE")]
+    profile: Option<String>,
+
+    /// Configuration file to use instead of config.toml in the configuration directory
+    #[arg(long, global = true, env = "FIT_CONNECT_CONFIG", value_name = "FILE")]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -36,14 +62,495 @@ enum StatsOption {
     RecentRide,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+enum PeriodOption {
+    /// Since Monday
+    Week,
+    /// Since the first day of the month
+    Month,
+}
+
+impl PeriodOption {
+    /// Returns the first day of the current period.
+    fn start(self) -> NaiveDate {
+        let today = Local::now().date_naive();
+        match self {
+            PeriodOption::Week => {
+                today - chrono::Days::new(today.weekday().num_days_from_monday().into())
+            }
+            PeriodOption::Month => today.with_day(1).unwrap_or(today),
+        }
+    }
+}
+
+/// Selection of a single weight when a period has several readings
+#[derive(Args)]
+struct PolicyArgs {
+    /// How to pick the weight from the readings of the period [default: sync.policy]
+    #[arg(short, long, value_enum)]
+    policy: Option<Policy>,
+    /// Weight given to each new reading by the trend policy, between 0 and 1
+    /// [default: sync.smoothing]
+    #[arg(long, value_parser = parse_smoothing)]
+    smoothing: Option<f64>,
+}
+
+impl PolicyArgs {
+    /// Returns the library policy for the selected option, falling back to the
+    /// sync settings of the configuration.
+    fn policy(&self, sync: &SyncConfig) -> WeightPolicy {
+        self.policy
+            .unwrap_or(sync.policy)
+            .weight_policy(self.smoothing.unwrap_or(sync.smoothing))
+    }
+}
+
+#[derive(Subcommand)]
+enum WithingsCommands {
+    /// Get every body measurement, one record per weigh-in
+    Measures {
+        /// The days to get the measurements from 1 == current day, 2 == previous
+        #[arg(short, long, default_value_t = 1, conflicts_with = "start")]
+        last_days: i64,
+        #[command(flatten)]
+        range: DateRange,
+    },
+    /// Get daily activity summaries (steps, distance, calories, elevation)
+    Activity {
+        /// The days to get the summaries from 1 == current day, 2 == previous
+        #[arg(short, long, default_value_t = 1, conflicts_with = "start")]
+        last_days: i64,
+        #[command(flatten)]
+        range: DateRange,
+    },
+    /// Get nightly sleep summaries (duration, stages, score, heart and breathing rate)
+    Sleep {
+        /// The days to get the summaries from 1 == current day, 2 == previous
+        #[arg(short, long, default_value_t = 1, conflicts_with = "start")]
+        last_days: i64,
+        #[command(flatten)]
+        range: DateRange,
+        /// Include the sleep stage segments of every night
+        #[arg(short, long)]
+        stages: bool,
+    },
+    /// Get workouts and optionally create them in Strava as manual activities
+    Workouts {
+        /// The days to get the workouts from 1 == current day, 2 == previous
+        #[arg(short, long, default_value_t = 1, conflicts_with = "start")]
+        last_days: i64,
+        #[command(flatten)]
+        range: DateRange,
+        /// Create an activity in Strava for every workout not already there,
+        /// requires the activity:write scope
+        #[arg(short = 'S', long, conflicts_with = "end")]
+        strava_sync: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum StravaCommands {
+    /// List your activities, newest first unless --after is given
+    Activities {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Stop after this many activities
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Output format [default: output.format]
+        #[arg(short, long, value_enum)]
+        format: Option<OutputFormat>,
+    },
+    /// Get an activity with its laps, splits and efforts, and optionally its streams
+    Activity {
+        /// Identifier of the activity
+        id: i64,
+        /// Include these streams, comma separated
+        #[arg(
+            short,
+            long,
+            value_enum,
+            value_delimiter = ',',
+            conflicts_with = "all_streams"
+        )]
+        streams: Vec<StreamType>,
+        /// Include every stream
+        #[arg(short = 'A', long)]
+        all_streams: bool,
+        /// Number of samples per stream, every sample if not set
+        #[arg(short, long, value_enum)]
+        resolution: Option<Resolution>,
+    },
+    /// Create a manual activity, requires the activity:write scope
+    Create {
+        /// Name of the activity
+        #[arg(short, long)]
+        name: String,
+        /// Sport type of the activity, e.g. Run or GravelRide
+        #[arg(short = 't', long)]
+        sport_type: String,
+        /// Local start time, e.g. 2024-03-01T07:30:00
+        #[arg(short, long)]
+        start: NaiveDateTime,
+        /// Elapsed time as HH:MM:SS, MM:SS or seconds
+        #[arg(short, long, value_parser = parse_elapsed)]
+        elapsed: i64,
+        /// Distance in meters
+        #[arg(short = 'D', long)]
+        distance: Option<f64>,
+        /// Description of the activity
+        #[arg(short, long)]
+        description: Option<String>,
+        /// Mark the activity as done on a trainer
+        #[arg(long)]
+        trainer: bool,
+        /// Mark the activity as a commute
+        #[arg(long)]
+        commute: bool,
+    },
+    /// Update an activity, requires the activity:write scope
+    Update {
+        /// Identifier of the activity
+        id: i64,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+    /// Apply the same changes to every activity that meets a filter, requires the activity:write scope
+    BatchUpdate {
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        edit: EditArgs,
+        /// Show the changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List your starred segments
+    Segments,
+    /// Get a segment with your personal record on it
+    Segment {
+        /// Identifier of the segment
+        id: i64,
+    },
+    /// Get your efforts on a segment with the personal record progression, requires a subscription
+    SegmentEfforts {
+        /// Identifier of the segment
+        id: i64,
+        /// Only efforts started on or after this day, e.g. 2024-03-01
+        #[arg(long)]
+        after: Option<NaiveDate>,
+        /// Only efforts started before this day
+        #[arg(long)]
+        before: Option<NaiveDate>,
+        /// Print the record progression and the gap to the record instead of JSON
+        #[arg(short, long)]
+        summary: bool,
+    },
+    /// List the clubs you are a member of
+    Clubs,
+    /// List the members of a club
+    ClubMembers {
+        /// Identifier of the club
+        id: i64,
+    },
+    /// Get the recent activities of a club's members
+    ClubFeed {
+        /// Identifier of the club
+        id: i64,
+        /// Stop after this many activities
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Total the distance, time and elevation of every club member over a period
+    ///
+    /// Strava does not document a time filter for the club feed and does not
+    /// share when club activities started, so the period is passed on as a
+    /// hint that cannot be checked; if Strava ignores it, the whole recent feed
+    /// is totalled.
+    ClubLeaderboard {
+        /// Identifier of the club
+        id: i64,
+        /// Period to total
+        #[arg(short, long, value_enum, default_value = "week")]
+        period: PeriodOption,
+        /// Total from this day instead, e.g. 2024-03-01
+        #[arg(long, conflicts_with = "period")]
+        after: Option<NaiveDate>,
+        /// Only activities of this sport type, e.g. Run or Ride; may be repeated
+        #[arg(short = 't', long = "type", value_name = "SPORT_TYPE")]
+        sport_types: Vec<String>,
+        /// Print a ranked table instead of JSON
+        #[arg(long)]
+        table: bool,
+    },
+    /// List your saved routes
+    Routes,
+    /// Export saved routes to GPX or TCX files
+    RouteExport {
+        /// Identifiers of the routes
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        ids: Vec<String>,
+        /// Export every saved route
+        #[arg(short, long)]
+        all: bool,
+        /// File format
+        #[arg(short, long, value_enum, default_value = "gpx")]
+        format: RouteFormat,
+        /// Directory to write the files to
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Show the remaining Strava request allowance of the application
+    Quota,
+    /// Back up the whole account to a directory, fetching only what changed since the last run
+    Backup {
+        /// Backup directory, created if missing
+        dir: PathBuf,
+    },
+    /// Report gear mileage and flag items past their retirement distance
+    ///
+    /// Exits with status 2 when an item reaches its retirement distance, so it
+    /// can be used to alert from a scheduled job.
+    Gear {
+        /// The days counted in the period distance 1 == current day, 2 == previous
+        #[arg(short, long, default_value_t = 30, conflicts_with = "after")]
+        days: i64,
+        /// Start the period on this day instead, e.g. 2024-01-01
+        #[arg(long)]
+        after: Option<NaiveDate>,
+        /// End the period before this day, defaults to now
+        #[arg(long, requires = "after")]
+        before: Option<NaiveDate>,
+        /// Retirement distance of every shoe, in km
+        #[arg(long, value_name = "KM")]
+        shoe_limit: Option<f64>,
+        /// Retirement distance of every bike, in km
+        #[arg(long, value_name = "KM")]
+        bike_limit: Option<f64>,
+        /// Retirement distance of a single item; may be repeated
+        #[arg(long = "limit", value_name = "GEAR_ID=KM", value_parser = parse_gear_limit)]
+        limits: Vec<(String, f64)>,
+        /// Share of the retirement distance from which a warning is printed
+        #[arg(long, default_value_t = DEFAULT_WARNING_RATIO)]
+        warn_at: f64,
+        /// Also exit with status 2 on warnings
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Upload FIT, TCX or GPX files, optionally gzip compressed, requires the activity:write scope
+    Upload {
+        /// Files to upload
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Name of the activities, Strava picks one if not set
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Description of the activities
+        #[arg(short, long)]
+        description: Option<String>,
+        /// Mark the activities as done on a trainer
+        #[arg(long)]
+        trainer: bool,
+        /// Mark the activities as commutes
+        #[arg(long)]
+        commute: bool,
+        /// Seconds to wait for Strava to process each file, 0 to not wait
+        #[arg(short, long, default_value_t = 60)]
+        timeout: u64,
+    },
+}
+
+/// Criteria activities must meet to be selected
+#[derive(Args)]
+struct FilterArgs {
+    /// Only activities started on or after this day, e.g. 2024-03-01
+    #[arg(long)]
+    after: Option<NaiveDate>,
+    /// Only activities started before this day
+    #[arg(long)]
+    before: Option<NaiveDate>,
+    /// Only activities of this sport type, e.g. Run or GravelRide; may be repeated
+    #[arg(short = 't', long = "type", value_name = "SPORT_TYPE")]
+    sport_types: Vec<String>,
+    /// Only activities whose name matches this pattern, * matches any text, ? a single character
+    #[arg(long = "match", value_name = "PATTERN")]
+    name_pattern: Option<String>,
+    /// Only activities at least this long, in meters
+    #[arg(long, value_name = "METERS")]
+    min_distance: Option<f64>,
+    /// Only activities at most this long, in meters
+    #[arg(long, value_name = "METERS")]
+    max_distance: Option<f64>,
+}
+
+impl FilterArgs {
+    /// Returns the library filter for the given criteria.
+    fn filter(self) -> ActivityFilter {
+        ActivityFilter {
+            after: self.after.map(|day| local_midnight(day) - 1),
+            before: self.before.map(local_midnight),
+            sport_types: self.sport_types,
+            min_distance: self.min_distance,
+            max_distance: self.max_distance,
+            name_pattern: self.name_pattern,
+        }
+    }
+}
+
+/// Changes to apply to existing activities, at least one is required
+#[derive(Args)]
+#[group(required = true, multiple = true)]
+struct EditArgs {
+    /// New name
+    #[arg(long)]
+    name: Option<String>,
+    /// New description
+    #[arg(long)]
+    description: Option<String>,
+    /// New sport type, e.g. Run or GravelRide
+    #[arg(long)]
+    sport_type: Option<String>,
+    /// Gear to use, e.g. g1234567, or none to remove the gear
+    #[arg(long = "gear", value_name = "GEAR_ID")]
+    gear_id: Option<String>,
+    /// Mark or unmark as a commute
+    #[arg(long)]
+    commute: Option<bool>,
+    /// Mark or unmark as done on a trainer
+    #[arg(long)]
+    trainer: Option<bool>,
+    /// Hide from or show in the home feed
+    #[arg(long)]
+    hide_from_home: Option<bool>,
+    /// Who can see the activity
+    #[arg(long, value_enum)]
+    visibility: Option<Visibility>,
+}
+
+impl EditArgs {
+    /// Returns the library update for the given changes.
+    fn update(&self) -> ActivityUpdate {
+        ActivityUpdate {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            sport_type: self.sport_type.clone(),
+            gear_id: self.gear_id.clone(),
+            commute: self.commute,
+            trainer: self.trainer,
+            hide_from_home: self.hide_from_home,
+            visibility: self.visibility,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// List the profiles and the services they have credentials for
+    List,
+    /// Add a profile, or the credentials of another service to an existing one
+    #[command(group(
+        ArgGroup::new("credentials")
+            .args(["strava_client_id", "withings_client_id"])
+            .required(true)
+            .multiple(true)
+    ))]
+    Add {
+        /// Name of the profile, made of letters, digits, '-' and '_'
+        name: String,
+        /// Client ID of the athlete's Strava application
+        #[arg(long)]
+        strava_client_id: Option<String>,
+        /// Client secret of the athlete's Strava application, read from the
+        /// standard input if not set. Prefer the environment variable, as
+        /// other users can see the options in the process list
+        #[arg(long, env = "FIT_CONNECT_STRAVA_CLIENT_SECRET", hide_env_values = true)]
+        strava_client_secret: Option<String>,
+        /// Client ID of the user's Withings application
+        #[arg(long)]
+        withings_client_id: Option<String>,
+        /// Client secret of the user's Withings application, read from the
+        /// standard input if not set. Prefer the environment variable, as
+        /// other users can see the options in the process list
+        #[arg(
+            long,
+            env = "FIT_CONNECT_WITHINGS_CLIENT_SECRET",
+            hide_env_values = true
+        )]
+        withings_client_secret: Option<String>,
+    },
+    /// Remove a profile and delete its tokens
+    Remove {
+        /// Name of the profile
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Create the configuration file by answering a few questions
+    Init {
+        /// Replace an existing configuration file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the configuration in effect, with the client secrets hidden
+    Show,
+    /// Check the configuration file and report every problem found
+    Validate,
+}
+
+/// Explicit range of days, used instead of a day offset
+#[derive(Args)]
+struct DateRange {
+    /// First day to include, e.g. 2024-03-01
+    #[arg(long)]
+    start: Option<NaiveDate>,
+    /// Last day to include, defaults to today
+    #[arg(long, requires = "start")]
+    end: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// Returns the Withings window for the range, or for the last `day_offset` days.
+    fn window(&self, day_offset: i64) -> Window {
+        match self.start {
+            Some(start) => {
+                Window::days(start, self.end.unwrap_or_else(|| Local::now().date_naive()))
+            }
+            None => Window::UpdatedSince(get_day_before_timestamp(day_offset)),
+        }
+    }
+
+    /// Returns the Unix timestamp the range starts at, or that of the last `day_offset` days.
+    fn since(&self, day_offset: i64) -> i64 {
+        match self.start {
+            Some(start) => local_midnight(start),
+            None => get_day_before_timestamp(day_offset),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
+    /// Get the weight from Withings and optionally sync it to Strava
+    #[command(args_conflicts_with_subcommands = true)]
     Withings {
+        /// The days to get the weight from 1 == current day, 2 == previous
+        /// [default: sync.last_days]
         #[arg(short, long)]
-        last_weight: i64,
+        last_weight: Option<i64>,
         #[arg(short, long)]
         strava_sync: bool,
+        /// Authorize fit-connect-rs with Withings in the browser and save the tokens
+        #[arg(short = 'r', long, conflicts_with = "strava_sync")]
+        register: bool,
+        #[command(flatten)]
+        policy: PolicyArgs,
+        #[command(subcommand)]
+        command: Option<WithingsCommands>,
     },
+    /// Get data from Strava
+    #[command(args_conflicts_with_subcommands = true)]
     Strava {
         #[arg(
             short = 'r',
@@ -55,9 +562,701 @@ enum Commands {
         get_athlete: bool,
         #[arg(short = 's', long, value_name = "OPTION")]
         get_stats: Option<StatsOption>,
+        #[command(subcommand)]
+        command: Option<StravaCommands>,
+    },
+    /// Manage the profiles of the athletes served by this install
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Create, show or check the configuration file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Sync the weight from a measurement source to one or more profiles
+    Sync {
+        /// Service to read the weight from [default: sync.from, or withings]
+        #[arg(short, long, value_enum)]
+        from: Option<SourceKind>,
+        /// Services to write the weight to [default: sync.to, or strava]
+        #[arg(short, long, value_enum, num_args = 1..)]
+        to: Vec<SinkKind>,
+        /// The days to get the weight from 1 == current day, 2 == previous
+        /// [default: sync.last_days]
+        #[arg(short, long)]
+        last_weight: Option<i64>,
+        #[command(flatten)]
+        policy: PolicyArgs,
     },
 }
 
+/// Unwraps the result of a command, printing the diagnostic and exiting on failure.
+fn exit_on_error<T>(result: miette::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{:?}", e);
+        exit(1)
+    })
+}
+
+/// Returns the Unix timestamp of the local midnight starting a day.
+fn local_midnight(day: NaiveDate) -> i64 {
+    day.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|t| t.timestamp())
+        .unwrap_or_default()
+}
+
+/// Parses an elapsed time given as `HH:MM:SS`, `MM:SS` or seconds.
+fn parse_elapsed(value: &str) -> Result<i64, String> {
+    let parts = value
+        .split(':')
+        .map(|part| part.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid elapsed time: {}", value))?;
+
+    match parts.as_slice() {
+        [seconds] => Ok(*seconds),
+        [minutes, seconds] => Ok(minutes * 60 + seconds),
+        [hours, minutes, seconds] => Ok(hours * 3600 + minutes * 60 + seconds),
+        _ => Err(format!("invalid elapsed time: {}", value)),
+    }
+}
+
+/// Parses a smoothing factor of the trend policy, between 0 and 1.
+fn parse_smoothing(value: &str) -> Result<f64, String> {
+    let smoothing = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid smoothing: {}", value))?;
+
+    if is_valid_smoothing(smoothing) {
+        Ok(smoothing)
+    } else {
+        Err(format!("smoothing must be between 0 and 1, not {}", value))
+    }
+}
+
+/// Parses a retirement distance given as `GEAR_ID=KM`.
+fn parse_gear_limit(value: &str) -> Result<(String, f64), String> {
+    let (id, km) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected GEAR_ID=KM, got {}", value))?;
+    let km = km
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid distance: {}", km))?;
+
+    Ok((id.trim().to_string(), km))
+}
+
+/// Formats a number of seconds as `H:MM:SS` or `M:SS`.
+fn format_elapsed(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.abs();
+    if seconds >= 3600 {
+        format!(
+            "{}{}:{:02}:{:02}",
+            sign,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}{}:{:02}", sign, seconds / 60, seconds % 60)
+    }
+}
+
+/// Prints items as they are received in the selected format, exiting on the first error.
+fn print_items<T, I>(items: I, format: OutputFormat)
+where
+    T: Serialize,
+    I: Iterator<Item = miette::Result<T>>,
+{
+    match format {
+        OutputFormat::Json => {
+            let items = exit_on_error(items.collect::<miette::Result<Vec<T>>>());
+            let j = to_colored_json_auto(&items);
+            println!("{}", j.unwrap());
+        }
+        OutputFormat::Ndjson => {
+            for item in items {
+                let item = exit_on_error(item);
+                println!("{}", serde_json::to_string(&item).unwrap());
+            }
+        }
+    }
+}
+
+/// Runs a Strava subcommand, exiting on failure.
+fn run_strava(session: &StravaSession, command: StravaCommands, output: &OutputConfig) {
+    match command {
+        StravaCommands::Activities {
+            filter,
+            limit,
+            format,
+        } => {
+            let activities = exit_on_error(session.activities(filter.filter()))
+                .map(|activity| activity.map_err(Into::into))
+                .take(limit.unwrap_or(usize::MAX));
+            print_items(activities, format.unwrap_or(output.format));
+        }
+        StravaCommands::Activity {
+            id,
+            streams,
+            all_streams,
+            resolution,
+        } => {
+            let mut activity = exit_on_error(session.get_activity(id));
+            let types = if all_streams {
+                StreamType::ALL.to_vec()
+            } else {
+                streams
+            };
+            if !types.is_empty() {
+                activity.streams = Some(exit_on_error(
+                    session.get_activity_streams(id, &types, resolution),
+                ));
+            }
+            let j = to_colored_json_auto(&activity);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::Create {
+            name,
+            sport_type,
+            start,
+            elapsed,
+            distance,
+            description,
+            trainer,
+            commute,
+        } => {
+            let activity = NewActivity {
+                name,
+                sport_type,
+                start_date_local: start,
+                elapsed_time: elapsed,
+                description,
+                distance,
+                trainer,
+                commute,
+            };
+            let created = exit_on_error(session.create_manual_activity(&activity));
+            let j = to_colored_json_auto(&created);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::Update { id, edit } => {
+            let updated = exit_on_error(session.update_activity(id, &edit.update()));
+            let j = to_colored_json_auto(&updated);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::BatchUpdate {
+            filter,
+            edit,
+            dry_run,
+        } => {
+            let update = edit.update();
+            let edits = exit_on_error(session.plan_edits(filter.filter(), &update));
+
+            for edit in &edits {
+                println!(
+                    "{} {} {}",
+                    edit.id,
+                    edit.start_date
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    edit.name
+                );
+                for change in &edit.changes {
+                    println!(
+                        "  {}: {:?} -> {:?}",
+                        change.field, change.before, change.after
+                    );
+                }
+            }
+
+            if dry_run {
+                println!("{} activities would be updated", edits.len());
+            } else {
+                exit_on_error(session.apply_edits(&edits, &update));
+                println!("{} activities updated", edits.len());
+            }
+        }
+        StravaCommands::Segments => {
+            let segments = exit_on_error(session.get_starred_segments());
+            let j = to_colored_json_auto(&segments);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::Segment { id } => {
+            let segment = exit_on_error(session.get_segment(id));
+            let j = to_colored_json_auto(&segment);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::SegmentEfforts {
+            id,
+            after,
+            before,
+            summary,
+        } => {
+            let start = after.map(|day| day.and_time(NaiveTime::MIN));
+            let end = before.map(|day| day.and_time(NaiveTime::MIN) - Duration::from_secs(1));
+            let history = exit_on_error(session.get_segment_history(id, start, end));
+
+            if !summary {
+                let j = to_colored_json_auto(&history);
+                println!("{}", j.unwrap());
+            } else if let (Some(latest), Some(gap)) = (&history.latest, history.gap_to_pr) {
+                println!("{} efforts, record progression:", history.efforts.len());
+                for effort in &history.pr_progression {
+                    println!(
+                        "  {}  {}",
+                        effort.start_date.with_timezone(&Local).format("%Y-%m-%d"),
+                        format_elapsed(effort.elapsed_time)
+                    );
+                }
+                println!(
+                    "latest {}  {}  (+{} from the record)",
+                    latest.start_date.with_timezone(&Local).format("%Y-%m-%d"),
+                    format_elapsed(latest.elapsed_time),
+                    format_elapsed(gap)
+                );
+            } else {
+                println!("No efforts on segment {}", id);
+            }
+        }
+        StravaCommands::Clubs => {
+            let clubs = exit_on_error(session.get_clubs());
+            let j = to_colored_json_auto(&clubs);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::ClubMembers { id } => {
+            let members = exit_on_error(session.get_club_members(id));
+            let j = to_colored_json_auto(&members);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::ClubFeed { id, limit } => {
+            let activities = exit_on_error(session.get_club_activities(id, None, limit));
+            let j = to_colored_json_auto(&activities);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::ClubLeaderboard {
+            id,
+            period,
+            after,
+            sport_types,
+            table,
+        } => {
+            let start = after.unwrap_or_else(|| period.start());
+            let ranking = exit_on_error(session.get_club_leaderboard(
+                id,
+                local_midnight(start),
+                &sport_types,
+            ));
+
+            if table {
+                let units = output.units;
+                let (_, distance_unit) = units.distance(0.0);
+                let (_, elevation_unit) = units.elevation(0.0);
+                println!("Since {}", start);
+                println!(
+                    "{:>4}  {:<24} {:>5} {:>10} {:>10} {:>8}",
+                    "#",
+                    "Athlete",
+                    "Acts",
+                    distance_unit,
+                    "Moving",
+                    format!("Elev {}", elevation_unit)
+                );
+                for (rank, member) in ranking.iter().enumerate() {
+                    println!(
+                        "{:>4}  {:<24} {:>5} {:>10.1} {:>10} {:>8.0}",
+                        rank + 1,
+                        member.athlete,
+                        member.activities,
+                        units.distance(member.distance).0,
+                        format_elapsed(member.moving_time),
+                        units.elevation(member.elevation_gain).0
+                    );
+                }
+            } else {
+                let j = to_colored_json_auto(&ranking);
+                println!("{}", j.unwrap());
+            }
+        }
+        StravaCommands::Routes => {
+            let routes = exit_on_error(session.get_routes());
+            let j = to_colored_json_auto(&routes);
+            println!("{}", j.unwrap());
+        }
+        StravaCommands::RouteExport {
+            ids,
+            all,
+            format,
+            dir,
+        } => {
+            let routes = if all {
+                exit_on_error(session.get_routes())
+            } else {
+                ids.iter()
+                    .map(|id| exit_on_error(session.get_route(id)))
+                    .collect()
+            };
+            for route in &routes {
+                let path = exit_on_error(session.save_route(route, format, &dir));
+                println!("{}: {}", route.name, path.display());
+            }
+        }
+        StravaCommands::Quota => {
+            let quota = exit_on_error(session.get_quota());
+            println!(
+                "15 minutes: {} of {} left, resets at {}",
+                quota.short.remaining(),
+                quota.short.limit,
+                quota.short_reset_at.with_timezone(&Local).format("%H:%M")
+            );
+            println!(
+                "Daily:      {} of {} left, resets at {}",
+                quota.daily.remaining(),
+                quota.daily.limit,
+                quota
+                    .daily_reset_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+            );
+            if let (Some(short), Some(daily)) = (quota.read_short, quota.read_daily) {
+                println!(
+                    "Reads:      {} of {} left in 15 minutes, {} of {} today",
+                    short.remaining(),
+                    short.limit,
+                    daily.remaining(),
+                    daily.limit
+                );
+            }
+        }
+        StravaCommands::Backup { dir } => {
+            let report = exit_on_error(session.backup(&dir));
+            let j = to_colored_json_auto(&report);
+            println!("{}", j.unwrap());
+            if !report.failures.is_empty() {
+                exit(1);
+            }
+        }
+        StravaCommands::Gear {
+            days,
+            after,
+            before,
+            shoe_limit,
+            bike_limit,
+            limits,
+            warn_at,
+            strict,
+        } => {
+            let thresholds = GearThresholds {
+                shoes: shoe_limit.map(|km| km * 1000.0),
+                bikes: bike_limit.map(|km| km * 1000.0),
+                overrides: limits
+                    .into_iter()
+                    .map(|(id, km)| (id, km * 1000.0))
+                    .collect(),
+                warning_ratio: warn_at,
+            };
+            let after = after.map_or_else(|| get_day_before_timestamp(days), local_midnight);
+            let before = before.map_or_else(|| Local::now().timestamp(), local_midnight);
+
+            let usage = exit_on_error(session.get_gear_usage(after, before, &thresholds));
+            let j = to_colored_json_auto(&usage);
+            println!("{}", j.unwrap());
+
+            let mut alert = false;
+            for gear in &usage {
+                let label = match gear.status {
+                    GearStatus::Ok => continue,
+                    GearStatus::Warning => {
+                        alert |= strict;
+                        "warning"
+                    }
+                    GearStatus::Retire => {
+                        alert = true;
+                        "retire"
+                    }
+                };
+                let (distance, unit) = output.units.distance(gear.distance);
+                let (threshold, _) = output.units.distance(gear.threshold.unwrap_or_default());
+                eprintln!(
+                    "{}: {} {} at {:.0} {} of {:.0} {}",
+                    label, gear.kind, gear.name, distance, unit, threshold, unit
+                );
+            }
+            if alert {
+                exit(2);
+            }
+        }
+        StravaCommands::Upload {
+            files,
+            name,
+            description,
+            trainer,
+            commute,
+            timeout,
+        } => {
+            let options = UploadOptions {
+                name,
+                description,
+                trainer,
+                commute,
+                external_id: None,
+            };
+            let mut failed = false;
+            for file in &files {
+                let upload = session.upload_file(file, &options).and_then(|upload| {
+                    session.wait_for_upload(upload, Duration::from_secs(timeout))
+                });
+                let upload = match upload {
+                    Ok(upload) => upload,
+                    Err(e) => {
+                        eprintln!("{}: {:?}", file.display(), e);
+                        failed = true;
+                        continue;
+                    }
+                };
+                match upload.outcome() {
+                    UploadOutcome::Created { activity_id } => {
+                        println!("{}: created activity {}", file.display(), activity_id)
+                    }
+                    UploadOutcome::Duplicate {
+                        activity_id: Some(activity_id),
+                    } => println!("{}: duplicate of activity {}", file.display(), activity_id),
+                    UploadOutcome::Duplicate { activity_id: None } => {
+                        println!("{}: duplicate of an existing activity", file.display())
+                    }
+                    UploadOutcome::Processing => println!(
+                        "{}: still processing as upload {}",
+                        file.display(),
+                        upload.id
+                    ),
+                    UploadOutcome::Failed { error } => {
+                        eprintln!("{}: failed: {}", file.display(), error);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                exit(1);
+            }
+        }
+    }
+}
+
+/// Syncs the weight from `from` to every service in `to`, exiting on failure.
+fn run_sync(
+    profile: Option<&Profile>,
+    config: &Config,
+    from: SourceKind,
+    to: &[SinkKind],
+    day_offset: i64,
+    policy: WeightPolicy,
+) {
+    let source = exit_on_error(from.source(profile, config));
+    let since = get_day_before_timestamp(day_offset);
+
+    for kind in to {
+        let sink = exit_on_error(kind.sink(profile, config));
+        println!("Syncing to {}...", sink.name());
+        let synced = exit_on_error(sync_weight(source.as_ref(), sink.as_ref(), since, policy));
+        let (weight, unit) = config.output.units.weight(synced.weight_kg);
+        println!(
+            "Weight updated in {} to {:.1} {}",
+            synced.sink, weight, unit
+        );
+    }
+}
+
+/// Asks the user to open the authorization page of a service.
+fn show_authorization_url(service: &str, url: &str) {
+    eprintln!(
+        "Open this URL in your browser to authorize fit-connect-rs with {}:\n{}\n",
+        service, url
+    );
+}
+
+/// Loads a profile by name.
+fn load_profile(name: &str) -> miette::Result<Profile> {
+    Ok(Profiles::load()?.get(name)?.clone())
+}
+
+/// Runs a profile management command, exiting on failure.
+fn run_profile(command: ProfileCommands) {
+    let mut profiles = exit_on_error(Profiles::load().map_err(Into::into));
+
+    match command {
+        ProfileCommands::List => {
+            for profile in profiles.iter() {
+                println!("{}: {}", profile.name, profile.services().join(", "));
+            }
+        }
+        ProfileCommands::Add {
+            name,
+            strava_client_id,
+            strava_client_secret,
+            withings_client_id,
+            withings_client_secret,
+        } => {
+            let credentials = |service: &str,
+                               client_id: Option<String>,
+                               client_secret: Option<String>|
+             -> miette::Result<Option<Credentials>> {
+                let Some(client_id) = client_id else {
+                    return Ok(None);
+                };
+                let client_secret = match client_secret {
+                    Some(client_secret) => client_secret,
+                    None => read_secret(&format!("{} client secret", service))?,
+                };
+                Ok(Some(Credentials {
+                    client_id,
+                    client_secret,
+                }))
+            };
+            let strava = exit_on_error(credentials(
+                "Strava",
+                strava_client_id,
+                strava_client_secret,
+            ));
+            let withings = exit_on_error(credentials(
+                "Withings",
+                withings_client_id,
+                withings_client_secret,
+            ));
+            let profile = exit_on_error(profiles.add(&name, strava, withings).map_err(Into::into));
+            println!("{}: {}", profile.name, profile.services().join(", "));
+            exit_on_error(profiles.save().map_err(Into::into));
+        }
+        ProfileCommands::Remove { name } => {
+            let profile = exit_on_error(profiles.remove(&name).map_err(Into::into));
+            println!("Removed profile {}", profile.name);
+        }
+    }
+}
+
+/// Asks a question on the terminal and returns the answer, or `default` if
+/// the answer is empty.
+fn prompt(question: &str, default: Option<&str>) -> miette::Result<Option<String>> {
+    match default {
+        Some(default) => print!("{} [{}]: ", question, default),
+        None => print!("{} (leave empty to skip): ", question),
+    }
+    io::stdout().flush().into_diagnostic()?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .into_diagnostic()?;
+    let answer = answer.trim();
+
+    if answer.is_empty() {
+        Ok(default.map(str::to_string))
+    } else {
+        Ok(Some(answer.to_string()))
+    }
+}
+
+/// Reads a secret from the standard input, asking for it on a terminal, so it
+/// never shows in the process list or the shell history.
+fn read_secret(name: &str) -> miette::Result<String> {
+    if io::stdin().is_terminal() {
+        print!("{}: ", name);
+        io::stdout().flush().into_diagnostic()?;
+    }
+
+    let mut secret = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut secret)
+        .into_diagnostic()?;
+    let secret = secret.trim();
+
+    if secret.is_empty() {
+        Err(miette::miette!("No {} given", name))
+    } else {
+        Ok(secret.to_string())
+    }
+}
+
+/// Asks for the credentials of a service, keeping each token file apart.
+fn prompt_service(name: &str, token_file: PathBuf) -> miette::Result<ServiceConfig> {
+    let client_id = prompt(&format!("{} client ID", name), None)?;
+    let client_secret = match client_id {
+        Some(_) => prompt(&format!("{} client secret", name), None)?,
+        None => None,
+    };
+    let token_file = prompt(
+        &format!("{} token file", name),
+        Some(&token_file.display().to_string()),
+    )?
+    .map(PathBuf::from);
+
+    Ok(ServiceConfig {
+        client_id,
+        client_secret,
+        token_file,
+        ..Default::default()
+    })
+}
+
+/// Asks for the settings of a new configuration file and writes it.
+fn init_config(path: &Path, force: bool) -> miette::Result<()> {
+    if path.exists() && !force {
+        return Err(config::ConfigError::Exists {
+            path: path.to_path_buf(),
+        }
+        .into());
+    }
+
+    let strava = prompt_service("Strava", config::STRAVA.token_path())?;
+    let withings = prompt_service("Withings", config::WITHINGS.token_path())?;
+    let units = match prompt("Units (metric or imperial)", Some("metric"))?.as_deref() {
+        Some("imperial") => Units::Imperial,
+        _ => Units::Metric,
+    };
+
+    let config = Config {
+        strava,
+        withings,
+        output: OutputConfig {
+            units,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    config.validate()?;
+    config.save(path)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Runs a configuration command, exiting on failure.
+fn run_config(path: Option<&Path>, command: ConfigCommands) {
+    match command {
+        ConfigCommands::Init { force } => {
+            let default_path = config::default_path();
+            exit_on_error(init_config(path.unwrap_or(&default_path), force));
+        }
+        ConfigCommands::Show => {
+            let config = exit_on_error(Config::load(path).map_err(Into::into));
+            let toml = exit_on_error(config.redacted().to_toml().map_err(Into::into));
+            print!("{}", toml);
+        }
+        ConfigCommands::Validate => {
+            let config = exit_on_error(Config::load(path).map_err(Into::into));
+            exit_on_error(config.validate().map_err(Into::into));
+            println!("The configuration is valid");
+        }
+    }
+}
+
 pub fn cli() {
     let cli = Cli::parse();
 
@@ -65,75 +1264,234 @@ pub fn cli() {
         simple_logger::SimpleLogger::new().env().init().unwrap();
     }
 
+    if let Some(Commands::Config { command }) = cli.command {
+        run_config(cli.config.as_deref(), command);
+        return;
+    }
+
+    let config = exit_on_error(Config::load(cli.config.as_deref()).map_err(Into::into));
+    let profile = cli
+        .profile
+        .as_deref()
+        .map(|name| exit_on_error(load_profile(name)));
+    let profile = profile.as_ref();
+
     match cli.command {
+        Some(Commands::Withings {
+            command: Some(WithingsCommands::Measures { last_days, range }),
+            ..
+        }) => {
+            let session = exit_on_error(withings_session(profile, &config.withings));
+            let window = range.window(last_days);
+            let records = exit_on_error(session.get_body_composition(window).map_err(Into::into));
+            let j = to_colored_json_auto(&records);
+            println!("{}", j.unwrap());
+        }
+        Some(Commands::Withings {
+            command: Some(WithingsCommands::Activity { last_days, range }),
+            ..
+        }) => {
+            let session = exit_on_error(withings_session(profile, &config.withings));
+            let summaries = exit_on_error(session.get_activity_summaries(range.window(last_days)));
+            let j = to_colored_json_auto(&summaries);
+            println!("{}", j.unwrap());
+        }
+        Some(Commands::Withings {
+            command:
+                Some(WithingsCommands::Sleep {
+                    last_days,
+                    range,
+                    stages,
+                }),
+            ..
+        }) => {
+            let session = exit_on_error(withings_session(profile, &config.withings));
+            let summaries =
+                exit_on_error(session.get_sleep_summaries(range.window(last_days), stages));
+            let j = to_colored_json_auto(&summaries);
+            println!("{}", j.unwrap());
+        }
+        Some(Commands::Withings {
+            command:
+                Some(WithingsCommands::Workouts {
+                    last_days,
+                    range,
+                    strava_sync,
+                }),
+            ..
+        }) => {
+            if strava_sync {
+                let source = exit_on_error(SourceKind::Withings.workout_source(profile, &config));
+                let sink = exit_on_error(SinkKind::Strava.activity_sink(profile, &config));
+                let since = range.since(last_days);
+                let report = exit_on_error(sync_workouts(source.as_ref(), sink.as_ref(), since));
+                for workout in &report.skipped {
+                    println!(
+                        "Skipping {} {}, already in {}",
+                        workout.sport, workout.start, report.sink
+                    );
+                }
+                for activity in &report.created {
+                    println!(
+                        "Created {} {} in {} as {}",
+                        activity.workout.sport, activity.workout.start, report.sink, activity.id
+                    );
+                }
+                println!(
+                    "{} created, {} already in {}",
+                    report.created.len(),
+                    report.skipped.len(),
+                    report.sink
+                );
+            } else {
+                let session = exit_on_error(withings_session(profile, &config.withings));
+                let workouts = exit_on_error(session.get_workouts(range.window(last_days)));
+                let j = to_colored_json_auto(&workouts);
+                println!("{}", j.unwrap());
+            }
+        }
+        Some(Commands::Withings {
+            register: true,
+            command: None,
+            ..
+        }) => {
+            let session = exit_on_error(withings_session(profile, &config.withings));
+            exit_on_error(
+                session
+                    .authorize(|url| show_authorization_url("Withings", url))
+                    .map_err(Into::into),
+            );
+            println!("Authorized with Withings");
+        }
         Some(Commands::Withings {
             last_weight,
             strava_sync,
+            policy,
+            command: None,
+            ..
         }) => {
-            let weight_in_kgs = get_and_format_weight(last_weight);
-            println!("weight: {:?}", weight_in_kgs);
-            println!("strava_sync: {:?}", strava_sync);
+            let last_weight = last_weight.unwrap_or(config.sync.last_days);
+            let policy = policy.policy(&config.sync);
             if strava_sync {
-                strava::sync_weight_to_strava(weight_in_kgs).expect("TODO: panic message");
+                run_sync(
+                    profile,
+                    &config,
+                    SourceKind::Withings,
+                    &[SinkKind::Strava],
+                    last_weight,
+                    policy,
+                );
+            } else {
+                let source = exit_on_error(SourceKind::Withings.source(profile, &config));
+                let weight = get_and_format_weight(
+                    source.as_ref(),
+                    last_weight,
+                    policy,
+                    config.output.units,
+                );
+                println!("weight: {}", weight);
             }
         }
+        Some(Commands::Sync {
+            from,
+            to,
+            last_weight,
+            policy,
+        }) => {
+            let from = from.or(config.sync.from).unwrap_or(SourceKind::Withings);
+            let to = if to.is_empty() {
+                config
+                    .sync
+                    .to
+                    .clone()
+                    .unwrap_or_else(|| vec![SinkKind::Strava])
+            } else {
+                to
+            };
+            let last_weight = last_weight.unwrap_or(config.sync.last_days);
+            let policy = policy.policy(&config.sync);
+            run_sync(profile, &config, from, &to, last_weight, policy)
+        }
+        Some(Commands::Profile { command }) => run_profile(command),
+        Some(Commands::Config { .. }) => unreachable!("handled before loading the configuration"),
+        Some(Commands::Strava {
+            command: Some(command),
+            ..
+        }) => {
+            let session = exit_on_error(strava_session(profile, &config.strava));
+            run_strava(&session, command, &config.output);
+        }
         Some(Commands::Strava {
             register,
             get_athlete,
             get_stats,
+            command: None,
         }) => {
+            let session = exit_on_error(strava_session(profile, &config.strava));
             if register {
-                strava::auth_strava().unwrap();
+                let missing_scopes = exit_on_error(
+                    session
+                        .authorize(|url| show_authorization_url("Strava", url))
+                        .map_err(Into::into),
+                );
+                if missing_scopes.is_empty() {
+                    println!("Authorized with Strava");
+                } else {
+                    eprintln!(
+                        "Strava did not grant {}; run `strava --register` again to approve them",
+                        missing_scopes.join(", ")
+                    );
+                }
             }
             if get_athlete {
-                let athlete = strava::get_authenticated_athlete().unwrap();
+                let athlete = exit_on_error(session.get_authenticated_athlete());
                 let j = to_colored_json_auto(&athlete);
                 println!("{}", j.unwrap());
             }
             if let Some(stats_option) = get_stats {
                 match stats_option {
                     StatsOption::All => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let j = to_colored_json_auto(&stats);
                         println!("{}", j.unwrap());
                     }
                     StatsOption::YtdRun => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let j = to_colored_json_auto(&stats.ytd_run_totals);
                         println!("{}", j.unwrap());
                     }
                     StatsOption::YtdRunMiles => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let miles = stats.ytd_run_totals.distance_in_miles();
                         println!("{:.2}", miles);
                     }
                     StatsOption::YtdRide => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let j = to_colored_json_auto(&stats.ytd_ride_totals);
                         println!("{}", j.unwrap());
                     }
                     StatsOption::YtdSwim => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let j = to_colored_json_auto(&stats.ytd_swim_totals);
                         println!("{}", j.unwrap());
                     }
                     StatsOption::RecentRun => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let j = to_colored_json_auto(&stats.recent_run_totals);
                         println!("{}", j.unwrap());
                     }
                     StatsOption::RecentRunMiles => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let miles = stats.recent_run_totals.distance_in_miles();
                         println!("{:.2}", miles);
                     }
                     StatsOption::RecentSwim => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let j = to_colored_json_auto(&stats.recent_swim_totals);
                         println!("{}", j.unwrap());
                     }
                     StatsOption::RecentRide => {
-                        let stats = strava::get_athlete_stats().unwrap();
+                        let stats = exit_on_error(session.get_athlete_stats());
                         let j = to_colored_json_auto(&stats.recent_ride_totals);
                         println!("{}", j.unwrap());
                     }
@@ -0,0 +1,48 @@
+//! # fit-connect-rs
+//! Pull data from fitness services and sync it to other fitness tools.
+//!
+//! # Description
+//!
+//! The library exposes a session object per service. A session holds the
+//! credentials for one account and is the entry point for every API call.
+//! Each service is behind a cargo feature so embedders only compile the
+//! providers they need:
+//!
+//! * `withings` - [`WithingsSession`] for the Withings measure API
+//! * `strava` - [`StravaSession`] for the Strava athlete API
+//! * `cli` - the `fit-connect-rs` command line tool
+//!
+//! Sessions implement the traits in [`modules::provider`], so any measurement
+//! source can be synced to any profile sink.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use fit_connect_rs::modules::provider::sync_weight;
+//! use fit_connect_rs::modules::weight::WeightPolicy;
+//! use fit_connect_rs::{StravaSession, WithingsSession};
+//!
+//! fn main() -> miette::Result<()> {
+//!     let withings = WithingsSession::from_env()?;
+//!     let strava = StravaSession::from_env()?;
+//!
+//!     let stats = strava.get_athlete_stats()?;
+//!     println!("YTD run miles: {}", stats.ytd_run_totals.distance_in_miles());
+//!
+//!     // Sync the morning weight from the last day
+//!     let since = fit_connect_rs::modules::withings::get_day_before_timestamp(1);
+//!     sync_weight(&withings, &strava, since, WeightPolicy::Morning)?;
+//!     Ok(())
+//! }
+//! ```
+
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod modules;
+#[cfg(feature = "cli")]
+mod utils;
+
+#[cfg(feature = "strava")]
+pub use modules::strava::StravaSession;
+#[cfg(feature = "withings")]
+pub use modules::withings::WithingsSession;
@@ -1,6 +1,3 @@
-mod cli;
-mod modules;
-mod utils;
 fn main() {
-    cli::cli();
+    fit_connect_rs::cli::cli();
 }
@@ -0,0 +1,812 @@
+//! Configuration file shared by the library and the command line
+//!
+//! Every setting lives in one TOML file, `config.toml` in the configuration
+//! directory unless another path is given:
+//!
+//! ```toml
+//! [strava]
+//! client_id = "72920"
+//! client_secret = "xxxx"
+//! token_file = "/home/me/.config/fit-connect-rs/strava.json"
+//!
+//! [withings]
+//! client_id = "xxxx"
+//! client_secret = "xxxx"
+//!
+//! [output]
+//! units = "imperial"
+//! format = "ndjson"
+//!
+//! [sync]
+//! policy = "trend"
+//! smoothing = 0.2
+//! to = ["strava"]
+//! ```
+//!
+//! Missing settings take their built-in value; the tokens are kept in
+//! `strava.json` and `withings.json` in the configuration directory, except
+//! that Strava tokens an earlier version left in `config.json` in the working
+//! directory are still used until they are moved. The credential and token
+//! file environment variables, e.g. `STRAVA_CLIENT_ID` or
+//! `WITHINGS_CONFIG_FILE`, override the file so existing setups keep working.
+//!
+//! The configuration directory is `$FIT_CONNECT_HOME` if set, otherwise
+//! `fit-connect-rs` in the user configuration directory (`$XDG_CONFIG_HOME`,
+//! `~/.config` or `%APPDATA%`).
+
+use log::warn;
+use miette::SourceSpan;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+use super::files::write_private;
+use super::provider::{SinkKind, SourceKind};
+use super::weight::{is_valid_smoothing, WeightPolicy, DEFAULT_SMOOTHING};
+
+/// Environment variable overriding the configuration directory
+pub const HOME_ENV: &str = "FIT_CONNECT_HOME";
+
+/// Text shown instead of a secret
+const REDACTED: &str = "********";
+
+/// Built-in settings of a service and the environment variables overriding them
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ServiceDefaults {
+    /// Environment variable name for the client ID
+    pub(crate) client_id_env: &'static str,
+    /// Environment variable name for the client secret
+    pub(crate) client_secret_env: &'static str,
+    /// Environment variable name for the token file
+    pub(crate) token_file_env: &'static str,
+    /// Token file in the configuration directory used when none is configured
+    pub(crate) token_file: &'static str,
+    /// Token file in the working directory used by earlier versions
+    pub(crate) legacy_token_file: Option<&'static str>,
+    /// Base URL of the API
+    pub(crate) api_url: &'static str,
+    /// URL of the OAuth authorization page
+    pub(crate) auth_url: &'static str,
+    /// URL of the OAuth token endpoint
+    pub(crate) token_url: &'static str,
+    /// Comma separated OAuth scopes requested
+    pub(crate) scope: &'static str,
+    /// Local port the OAuth redirect is received on
+    pub(crate) redirect_port: u16,
+}
+
+/// Built-in Strava settings
+pub(crate) const STRAVA: ServiceDefaults = ServiceDefaults {
+    client_id_env: "STRAVA_CLIENT_ID",
+    client_secret_env: "STRAVA_CLIENT_SECRET",
+    token_file_env: "STRAVA_CONFIG_FILE",
+    token_file: "strava.json",
+    legacy_token_file: Some("config.json"),
+    api_url: "https://www.strava.com/api/v3",
+    auth_url: "https://www.strava.com/oauth/authorize",
+    token_url: "https://www.strava.com/oauth/token",
+    scope: "read,profile:read_all,profile:write,activity:read_all,activity:write",
+    redirect_port: 8888,
+};
+
+/// Built-in Withings settings
+pub(crate) const WITHINGS: ServiceDefaults = ServiceDefaults {
+    client_id_env: "WITHINGS_CLIENT_ID",
+    client_secret_env: "WITHINGS_CLIENT_SECRET",
+    token_file_env: "WITHINGS_CONFIG_FILE",
+    token_file: "withings.json",
+    legacy_token_file: None,
+    api_url: "https://wbsapi.withings.net",
+    auth_url: "https://account.withings.com/oauth2_user/authorize2",
+    token_url: "https://wbsapi.withings.net/v2/oauth2",
+    scope: "user.info,user.metrics,user.activity",
+    redirect_port: 8888,
+};
+
+impl ServiceDefaults {
+    /// Returns the default token file in the configuration directory.
+    pub(crate) fn token_path(&self) -> PathBuf {
+        config_dir().join(self.token_file)
+    }
+
+    /// Returns the token file used when none is configured: the default one,
+    /// or the one an earlier version kept in the working directory as long as
+    /// the default one does not exist.
+    pub(crate) fn fallback_token_path(&self) -> PathBuf {
+        fallback_token_path(self.token_path(), self.legacy_token_file.map(Path::new))
+    }
+
+    /// Returns the token file named by the environment variable, or the
+    /// fallback one if it is not set.
+    #[cfg(any(feature = "strava", feature = "withings"))]
+    pub(crate) fn env_token_path(&self) -> PathBuf {
+        env::var_os(self.token_file_env)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| self.fallback_token_path())
+    }
+}
+
+/// Returns `legacy` if it is a file and `path` does not exist, otherwise `path`.
+fn fallback_token_path(path: PathBuf, legacy: Option<&Path>) -> PathBuf {
+    match legacy {
+        Some(legacy) if legacy.is_file() && !path.exists() => {
+            warn!(
+                "Using the tokens of an earlier version in {}; move them to {} to use them from any directory",
+                legacy.display(),
+                path.display()
+            );
+            legacy.to_path_buf()
+        }
+        _ => path,
+    }
+}
+
+/// Errors that can occur while loading or saving the configuration
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum ConfigError {
+    /// The configuration file cannot be read or written
+    #[error("Failed to access {path}")]
+    #[diagnostic(
+        code(config::io),
+        help("Create a configuration file with `fit-connect-rs config init`")
+    )]
+    Io {
+        /// Path of the configuration file
+        path: PathBuf,
+        /// The underlying error
+        #[source]
+        source: io::Error,
+    },
+
+    /// The configuration file would be overwritten
+    #[error("{path} already exists")]
+    #[diagnostic(code(config::exists), help("Edit it, or pass --force to replace it"))]
+    Exists {
+        /// Path of the configuration file
+        path: PathBuf,
+    },
+
+    /// The configuration file is not valid TOML or has unknown settings
+    #[error("Failed to parse {path}: {message}")]
+    #[diagnostic(code(config::parse))]
+    Parse {
+        /// Path of the configuration file
+        path: PathBuf,
+        /// Description of the problem
+        message: String,
+        /// Contents of the configuration file
+        #[source_code]
+        src: String,
+        /// Location of the problem in the file
+        #[label("here")]
+        span: Option<SourceSpan>,
+    },
+
+    /// The configuration cannot be written as TOML
+    #[error("Failed to serialize the configuration: {0}")]
+    #[diagnostic(code(config::serialize))]
+    Serialize(#[from] toml::ser::Error),
+
+    /// The settings are readable but inconsistent
+    #[error("Invalid configuration:\n{}", problems.join("\n"))]
+    #[diagnostic(code(config::invalid))]
+    Invalid {
+        /// Every problem found
+        problems: Vec<String>,
+    },
+}
+
+/// Returns the configuration directory.
+pub fn config_dir() -> PathBuf {
+    if let Some(home) = env::var_os(HOME_ENV) {
+        return PathBuf::from(home);
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .unwrap_or_default()
+        .join("fit-connect-rs")
+}
+
+/// Returns the path of the default configuration file.
+pub fn default_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// Settings of the application registered with a service
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+    /// Client ID of the application
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Client secret of the application
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    /// File caching the user's tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>,
+    /// Base URL of the API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    /// URL of the OAuth authorization page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_url: Option<String>,
+    /// URL of the OAuth token endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_url: Option<String>,
+    /// Comma separated OAuth scopes requested when authorizing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Local port the OAuth redirect is received on, part of the redirect URI
+    /// registered with the service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_port: Option<u16>,
+}
+
+impl ServiceConfig {
+    /// Overrides the credentials and token file with the environment variables
+    /// that are set, then fills the unset settings with the built-in values.
+    fn resolve(&mut self, defaults: &ServiceDefaults) {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+        if let Some(client_id) = var(defaults.client_id_env) {
+            self.client_id = Some(client_id);
+        }
+        if let Some(client_secret) = var(defaults.client_secret_env) {
+            self.client_secret = Some(client_secret);
+        }
+        if let Some(token_file) = var(defaults.token_file_env) {
+            self.token_file = Some(PathBuf::from(token_file));
+        }
+
+        self.token_file
+            .get_or_insert_with(|| defaults.fallback_token_path());
+        self.api_url
+            .get_or_insert_with(|| defaults.api_url.to_string());
+        self.auth_url
+            .get_or_insert_with(|| defaults.auth_url.to_string());
+        self.token_url
+            .get_or_insert_with(|| defaults.token_url.to_string());
+        self.scope.get_or_insert_with(|| defaults.scope.to_string());
+        self.redirect_port.get_or_insert(defaults.redirect_port);
+    }
+
+    /// Appends the problems of the section to `problems`.
+    fn check(&self, section: &str, problems: &mut Vec<String>) {
+        match (&self.client_id, &self.client_secret) {
+            (Some(_), None) => problems.push(format!("{}.client_secret is not set", section)),
+            (None, Some(_)) => problems.push(format!("{}.client_id is not set", section)),
+            _ => {}
+        }
+
+        let urls = [
+            ("api_url", &self.api_url),
+            ("auth_url", &self.auth_url),
+            ("token_url", &self.token_url),
+        ];
+        for (name, url) in urls {
+            if let Some(url) = url {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    problems.push(format!("{}.{} is not an HTTP URL: {}", section, name, url));
+                }
+            }
+        }
+
+        if self
+            .scope
+            .as_ref()
+            .is_some_and(|scope| scope.trim().is_empty())
+        {
+            problems.push(format!("{}.scope is empty", section));
+        }
+        if self.redirect_port == Some(0) {
+            problems.push(format!("{}.redirect_port must not be 0", section));
+        }
+    }
+}
+
+/// Units used by the text output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// Kilograms, kilometers and meters
+    #[default]
+    Metric,
+    /// Pounds, miles and feet
+    Imperial,
+}
+
+impl Units {
+    /// Converts a weight in kilograms and returns it with its unit.
+    pub fn weight(self, kg: f64) -> (f64, &'static str) {
+        match self {
+            Units::Metric => (kg, "kg"),
+            Units::Imperial => (kg * 2.204_622_621_8, "lb"),
+        }
+    }
+
+    /// Converts a distance in meters to kilometers or miles and returns it with its unit.
+    pub fn distance(self, meters: f64) -> (f64, &'static str) {
+        match self {
+            Units::Metric => (meters / 1000.0, "km"),
+            Units::Imperial => (meters / 1609.344, "mi"),
+        }
+    }
+
+    /// Converts an elevation in meters and returns it with its unit.
+    pub fn elevation(self, meters: f64) -> (f64, &'static str) {
+        match self {
+            Units::Metric => (meters, "m"),
+            Units::Imperial => (meters / 0.3048, "ft"),
+        }
+    }
+}
+
+/// Format of commands that print a list of items
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// A single pretty printed JSON array
+    #[default]
+    Json,
+    /// One JSON object per line, printed as soon as it is received
+    Ndjson,
+}
+
+/// Output settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Units used by the text output
+    pub units: Units,
+    /// Format of commands that print a list of items
+    pub format: OutputFormat,
+}
+
+/// How a single weight is picked from the readings of a period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// Use the most recent reading
+    #[default]
+    Latest,
+    /// Use the first reading of the most recent day (morning weight)
+    Morning,
+    /// Use the mean of all readings
+    Mean,
+    /// Use the median of all readings
+    Median,
+    /// Use the exponentially smoothed trend of all readings
+    Trend,
+}
+
+impl Policy {
+    /// Returns the weight policy, using `smoothing` for the trend.
+    pub fn weight_policy(self, smoothing: f64) -> WeightPolicy {
+        match self {
+            Policy::Latest => WeightPolicy::Latest,
+            Policy::Morning => WeightPolicy::Morning,
+            Policy::Mean => WeightPolicy::Mean,
+            Policy::Median => WeightPolicy::Median,
+            Policy::Trend => WeightPolicy::Trend { smoothing },
+        }
+    }
+}
+
+/// Weight sync settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Service the weight is read from, Withings if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<SourceKind>,
+    /// Services the weight is written to, Strava if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Vec<SinkKind>>,
+    /// Days to read the weight from, 1 is the current day
+    pub last_days: i64,
+    /// How the weight is picked from the readings of the period
+    pub policy: Policy,
+    /// Weight given to each new reading by the trend policy, between 0 and 1
+    pub smoothing: f64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            from: None,
+            to: None,
+            last_days: 1,
+            policy: Policy::default(),
+            smoothing: DEFAULT_SMOOTHING,
+        }
+    }
+}
+
+/// Every setting of the tool
+///
+/// # Examples
+///
+/// ```no_run
+/// use fit_connect_rs::modules::config::Config;
+/// use fit_connect_rs::StravaSession;
+///
+/// let config = Config::load(None)?;
+/// let session = StravaSession::from_config(&config.strava)?;
+/// # Ok::<(), miette::Report>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Strava settings
+    pub strava: ServiceConfig,
+    /// Withings settings
+    pub withings: ServiceConfig,
+    /// Output settings
+    pub output: OutputConfig,
+    /// Weight sync settings
+    pub sync: SyncConfig,
+}
+
+impl Config {
+    /// Loads a configuration file and applies the environment variables.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file, or `None` for the default file, which may
+    ///   be missing
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if the file cannot be read or parsed.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut config = match path {
+            Some(path) => Config::read(path)?,
+            None => {
+                let path = default_path();
+                if path.exists() {
+                    Config::read(&path)?
+                } else {
+                    Config::default()
+                }
+            }
+        };
+
+        config.resolve();
+        Ok(config)
+    }
+
+    /// Returns the built-in configuration with the environment variables applied.
+    pub fn from_env() -> Self {
+        let mut config = Config::default();
+        config.resolve();
+        config
+    }
+
+    /// Reads a configuration file as it is, without the environment variables.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if the file cannot be read or parsed.
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        toml::from_str(&contents).map_err(|e| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: e.message().to_string(),
+            span: e.span().map(SourceSpan::from),
+            src: contents,
+        })
+    }
+
+    /// Writes the configuration to a file readable by its owner only, as it
+    /// holds the client secrets.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        write_private(path, self.to_toml()?).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Returns the configuration as TOML.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Serialize` if a setting cannot be written as TOML.
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Returns a copy of the configuration with the client secrets hidden.
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        for service in [&mut config.strava, &mut config.withings] {
+            if service.client_secret.is_some() {
+                service.client_secret = Some(REDACTED.to_string());
+            }
+        }
+        config
+    }
+
+    /// Checks that the settings are consistent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::Invalid` listing every problem found.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        self.strava.check("strava", &mut problems);
+        self.withings.check("withings", &mut problems);
+
+        if self.strava.token_file.is_some() && self.strava.token_file == self.withings.token_file {
+            problems.push(
+                "strava.token_file and withings.token_file are the same file, so each \
+                 service would overwrite the tokens of the other"
+                    .to_string(),
+            );
+        }
+        if self.sync.to.as_ref().is_some_and(Vec::is_empty) {
+            problems.push("sync.to is empty".to_string());
+        }
+        if self.sync.last_days < 1 {
+            problems.push(format!(
+                "sync.last_days must be at least 1, not {}",
+                self.sync.last_days
+            ));
+        }
+        if !is_valid_smoothing(self.sync.smoothing) {
+            problems.push(format!(
+                "sync.smoothing must be between 0 and 1, not {}",
+                self.sync.smoothing
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid { problems })
+        }
+    }
+
+    /// Applies the environment variables and fills the unset settings.
+    fn resolve(&mut self) {
+        self.strava.resolve(&STRAVA);
+        self.withings.resolve(&WITHINGS);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Serializes the tests that change the process environment
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Every environment variable read by `Config::load`
+    const VARS: [&str; 7] = [
+        HOME_ENV,
+        "STRAVA_CLIENT_ID",
+        "STRAVA_CLIENT_SECRET",
+        "STRAVA_CONFIG_FILE",
+        "WITHINGS_CLIENT_ID",
+        "WITHINGS_CLIENT_SECRET",
+        "WITHINGS_CONFIG_FILE",
+    ];
+
+    /// Runs `f` in an empty configuration directory with only `vars` set,
+    /// restoring the environment afterwards.
+    pub(crate) fn with_env<T>(name: &str, vars: &[(&str, &str)], f: impl FnOnce(&Path) -> T) -> T {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let saved: Vec<_> = VARS.iter().map(|var| (*var, env::var_os(var))).collect();
+
+        let dir = env::temp_dir().join(format!("fit-connect-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for var in VARS {
+            env::remove_var(var);
+        }
+        env::set_var(HOME_ENV, &dir);
+        for (var, value) in vars {
+            env::set_var(var, value);
+        }
+
+        let result = f(&dir);
+
+        for (var, value) in saved {
+            match value {
+                Some(value) => env::set_var(var, value),
+                None => env::remove_var(var),
+            }
+        }
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    fn problems(config: &Config) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(ConfigError::Invalid { problems }) => problems,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        with_env("defaults", &[], |dir| {
+            let config = Config::load(None).unwrap();
+
+            assert_eq!(problems(&config), Vec::<String>::new());
+            assert_eq!(config.strava.token_file, Some(dir.join("strava.json")));
+            assert_eq!(config.withings.token_file, Some(dir.join("withings.json")));
+            assert_eq!(config.strava.api_url.as_deref(), Some(STRAVA.api_url));
+            assert_eq!(config.withings.redirect_port, Some(WITHINGS.redirect_port));
+        });
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let vars = [
+            ("STRAVA_CLIENT_ID", "env-id"),
+            ("STRAVA_CONFIG_FILE", "/tmp/env-strava.json"),
+            ("WITHINGS_CLIENT_ID", ""),
+        ];
+        with_env("overrides", &vars, |dir| {
+            fs::write(
+                dir.join("config.toml"),
+                "[strava]\nclient_id = \"file-id\"\nclient_secret = \"file-secret\"\n\
+                 token_file = \"/tmp/file-strava.json\"\n\n\
+                 [withings]\nclient_id = \"withings-id\"\nclient_secret = \"withings-secret\"\n",
+            )
+            .unwrap();
+
+            let config = Config::load(None).unwrap();
+
+            assert_eq!(config.strava.client_id.as_deref(), Some("env-id"));
+            assert_eq!(config.strava.client_secret.as_deref(), Some("file-secret"));
+            assert_eq!(
+                config.strava.token_file,
+                Some(PathBuf::from("/tmp/env-strava.json"))
+            );
+            // An empty variable does not override the file
+            assert_eq!(config.withings.client_id.as_deref(), Some("withings-id"));
+            assert_eq!(config.withings.token_file, Some(dir.join("withings.json")));
+        });
+    }
+
+    #[test]
+    fn explicit_path_must_exist() {
+        with_env("missing", &[], |dir| {
+            let result = Config::load(Some(&dir.join("other.toml")));
+            assert!(matches!(result, Err(ConfigError::Io { .. })));
+        });
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_setting() {
+        with_env("parse", &[], |dir| {
+            let path = dir.join("config.toml");
+            fs::write(&path, "[output]\nunits = \"metric\"\ncolour = true\n").unwrap();
+
+            match Config::load(Some(&path)) {
+                Err(ConfigError::Parse {
+                    span: Some(span), ..
+                }) => {
+                    assert_eq!(span.offset(), "[output]\nunits = \"metric\"\n".len());
+                }
+                other => panic!("expected a parse error, got {:?}", other),
+            }
+        });
+    }
+
+    #[test]
+    fn shared_token_file_is_reported() {
+        let mut config = Config::default();
+        config.strava.token_file = Some(PathBuf::from("tokens.json"));
+        config.withings.token_file = Some(PathBuf::from("tokens.json"));
+
+        let problems = problems(&config);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("strava.token_file and withings.token_file"));
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let mut config = Config::default();
+        config.strava.client_id = Some("id".to_string());
+        config.withings.client_secret = Some("secret".to_string());
+        config.strava.api_url = Some("www.strava.com".to_string());
+        config.withings.scope = Some(" ".to_string());
+        config.withings.redirect_port = Some(0);
+        config.sync.to = Some(Vec::new());
+        config.sync.last_days = 0;
+        config.sync.smoothing = 1.5;
+
+        assert_eq!(
+            problems(&config),
+            [
+                "strava.client_secret is not set",
+                "strava.api_url is not an HTTP URL: www.strava.com",
+                "withings.client_id is not set",
+                "withings.scope is empty",
+                "withings.redirect_port must not be 0",
+                "sync.to is empty",
+                "sync.last_days must be at least 1, not 0",
+                "sync.smoothing must be between 0 and 1, not 1.5",
+            ]
+        );
+    }
+
+    #[test]
+    fn redacted_hides_only_set_secrets() {
+        let mut config = Config::default();
+        config.strava.client_secret = Some("secret".to_string());
+
+        let redacted = config.redacted();
+        assert_eq!(redacted.strava.client_secret.as_deref(), Some(REDACTED));
+        assert_eq!(redacted.withings.client_secret, None);
+    }
+
+    #[test]
+    fn written_config_reads_back() {
+        let mut config = Config::default();
+        config.strava.client_id = Some("72920".to_string());
+        config.output.units = Units::Imperial;
+        config.sync.policy = Policy::Trend;
+
+        let read: Config = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(read, config);
+    }
+
+    #[test]
+    fn legacy_token_file_is_used_until_moved() {
+        with_env("legacy", &[], |dir| {
+            let path = dir.join("strava.json");
+            let legacy = dir.join("config.json");
+            assert_eq!(fallback_token_path(path.clone(), Some(&legacy)), path);
+
+            fs::write(&legacy, "{}").unwrap();
+            assert_eq!(fallback_token_path(path.clone(), Some(&legacy)), legacy);
+            assert_eq!(fallback_token_path(path.clone(), None), path);
+
+            fs::write(&path, "{}").unwrap();
+            assert_eq!(fallback_token_path(path.clone(), Some(&legacy)), path);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_config_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        with_env("private", &[], |dir| {
+            let path = dir.join("config.toml");
+            Config::default().save(&path).unwrap();
+
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        });
+    }
+}
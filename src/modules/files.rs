@@ -0,0 +1,111 @@
+//! File helpers shared by the modules that keep data on disk.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Permissions of files holding secrets: read and write by the owner only
+#[cfg(unix)]
+const PRIVATE_FILE_MODE: u32 = 0o600;
+
+/// Permissions of directories holding secrets: accessible by the owner only
+#[cfg(unix)]
+const PRIVATE_DIR_MODE: u32 = 0o700;
+
+/// Writes a file so that readers see either the old or the new contents, never
+/// a partial write, by writing a temporary file next to it and renaming it.
+///
+/// Missing parent directories are created.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+/// * `contents` - New contents of the file
+///
+/// # Errors
+///
+/// Returns an error if the directory, the temporary file or the rename fails.
+#[cfg(feature = "strava")]
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if let Some(parent) = parent(path) {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp = tmp_path(path);
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+/// Writes a file holding secrets atomically, readable by its owner only.
+///
+/// The temporary file is created with mode 0600 before anything is written to
+/// it, and missing parent directories with mode 0700, so the secrets are never
+/// visible to other users. Other platforms keep their default permissions.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+/// * `contents` - New contents of the file
+///
+/// # Errors
+///
+/// Returns an error if the directory, the temporary file or the rename fails.
+pub(crate) fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if let Some(parent) = parent(path) {
+        create_private_dir(parent)?;
+    }
+
+    let tmp = tmp_path(path);
+    let mut file = create_private(&tmp)?;
+    file.write_all(contents.as_ref())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// Creates a directory and its missing parents, accessible by the owner only.
+///
+/// Directories that already exist keep their permissions.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be created.
+pub(crate) fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(PRIVATE_DIR_MODE);
+    }
+    builder.create(path)
+}
+
+/// Creates or truncates a file readable by its owner only.
+fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(PRIVATE_FILE_MODE);
+        let file = options.open(path)?;
+        // The mode only applies to new files, e.g. not to a leftover temporary file
+        file.set_permissions(fs::Permissions::from_mode(PRIVATE_FILE_MODE))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options.open(path)
+}
+
+/// Returns the parent directory of a path, if it has one.
+fn parent(path: &Path) -> Option<&Path> {
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+}
+
+/// Returns the path of the temporary file written before a rename.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    tmp.into()
+}
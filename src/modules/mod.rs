@@ -1,2 +1,14 @@
+pub mod config;
+pub(crate) mod files;
+#[cfg(any(feature = "strava", feature = "withings"))]
+pub(crate) mod oauth;
+pub mod profile;
+pub mod provider;
+#[cfg(feature = "strava")]
+pub mod strava;
+#[cfg(any(feature = "strava", feature = "withings"))]
+pub(crate) mod token;
+pub mod weight;
+#[cfg(feature = "withings")]
 pub mod withings;
-pub mod strava;
\ No newline at end of file
+pub mod workout;
@@ -0,0 +1,458 @@
+//! Loopback OAuth authorization shared by the Strava and Withings sessions
+//!
+//! The user is sent to the authorization page of the service, which redirects
+//! the browser to `http://localhost:<port>` with an authorization code. A
+//! listener on that port captures the redirect, checks that it carries the
+//! random `state` sent with the request, so a forged redirect cannot plant
+//! another account's code or end the wait, and answers with a page telling
+//! the user whether the authorization worked.
+
+use log::{debug, info, warn};
+use rand::distributions::{Alphanumeric, DistString};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Length of the random `state` parameter
+const STATE_LENGTH: usize = 32;
+
+/// Time the user has to approve the application
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Time between two checks for the redirect
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time a connected browser has to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors that can occur while authorizing the application
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum OauthError {
+    /// The configured authorization URL cannot be parsed
+    #[error("Invalid authorization URL {url}: {message}")]
+    InvalidUrl { url: String, message: String },
+    /// The redirect port is in use or not allowed
+    #[error("Failed to listen on localhost:{port} for the authorization redirect")]
+    Listen {
+        port: u16,
+        #[source]
+        source: io::Error,
+    },
+    /// The redirect could not be received
+    #[error("Failed to receive the authorization redirect")]
+    Callback(#[source] io::Error),
+    /// The user did not approve the application in time
+    #[error("No authorization was received within {} minutes", CALLBACK_TIMEOUT.as_secs() / 60)]
+    Timeout,
+    /// The user refused the authorization or the service reported an error
+    #[error("The authorization was refused: {error}")]
+    Denied { error: String },
+    /// The redirect does not answer the request that was sent
+    #[error("The state of the authorization redirect does not match the request")]
+    StateMismatch,
+    /// The redirect carries no authorization code
+    #[error("The authorization redirect has no code")]
+    MissingCode,
+}
+
+/// Authorization request sent to a service
+pub(crate) struct Authorization<'a> {
+    /// Name of the service, shown to the user
+    pub(crate) service: &'a str,
+    /// URL of the OAuth authorization page
+    pub(crate) auth_url: &'a str,
+    /// Client ID of the application
+    pub(crate) client_id: &'a str,
+    /// Comma separated scopes requested
+    pub(crate) scope: &'a str,
+    /// Local port the redirect is received on
+    pub(crate) port: u16,
+    /// Additional query parameters required by the service
+    pub(crate) extra: &'a [(&'a str, &'a str)],
+}
+
+/// Authorization granted by the user
+#[derive(Debug)]
+pub(crate) struct Grant {
+    /// Code to exchange for the first tokens
+    pub(crate) code: String,
+    /// Requested scopes the user did not grant, if the service reports them
+    pub(crate) missing_scopes: Vec<String>,
+}
+
+impl Authorization<'_> {
+    /// Returns the redirect URI registered with the service.
+    pub(crate) fn redirect_uri(&self) -> String {
+        format!("http://localhost:{}", self.port)
+    }
+
+    /// Listens for the redirect, hands the authorization page to `prompt` and
+    /// waits for the user to approve the application.
+    ///
+    /// Requests whose `state` does not match are answered with an error page
+    /// and otherwise ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - Called with the URL of the authorization page once the
+    ///   listener is ready, to show it to the user
+    ///
+    /// # Returns
+    ///
+    /// The authorization code and the requested scopes that were not granted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `OauthError` if the port cannot be used, or the user refuses
+    /// or does not answer within five minutes.
+    pub(crate) fn run(&self, prompt: impl FnOnce(&str)) -> Result<Grant, OauthError> {
+        let state = Alphanumeric.sample_string(&mut rand::thread_rng(), STATE_LENGTH);
+        let url = self.url(&state)?;
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, self.port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|source| OauthError::Listen {
+                port: self.port,
+                source,
+            })?;
+
+        prompt(url.as_str());
+        info!("Listening on localhost:{} for the redirect", self.port);
+
+        let deadline = Instant::now() + CALLBACK_TIMEOUT;
+        loop {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Err(OauthError::Timeout);
+                    }
+                    sleep(ACCEPT_INTERVAL);
+                    continue;
+                }
+                Err(e) => return Err(OauthError::Callback(e)),
+            };
+
+            // A connection that fails, e.g. one the browser opened in advance
+            // and never used, must not end the wait for the redirect
+            match self.answer(stream, &state) {
+                Ok(Some(result)) => return result,
+                Ok(None) => {}
+                Err(e) => warn!("Failed to read a request on the redirect port: {}", e),
+            }
+        }
+    }
+
+    /// Builds the URL of the authorization page.
+    fn url(&self, state: &str) -> Result<Url, OauthError> {
+        let redirect_uri = self.redirect_uri();
+        let mut params = vec![
+            ("client_id", self.client_id),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("response_type", "code"),
+            ("scope", self.scope),
+            ("state", state),
+        ];
+        params.extend_from_slice(self.extra);
+
+        Url::parse_with_params(self.auth_url, &params).map_err(|e| OauthError::InvalidUrl {
+            url: self.auth_url.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    /// Reads a request of the browser and answers it.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of the authorization, or `None` if the request is not the
+    /// redirect of this authorization, e.g. the browser asking for a favicon or
+    /// a request with another `state`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `io::Error` if the request cannot be read.
+    fn answer(
+        &self,
+        stream: TcpStream,
+        state: &str,
+    ) -> io::Result<Option<Result<Grant, OauthError>>> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        // Read the headers so the browser is not cut off while still sending
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let query: HashMap<String, String> = Url::parse("http://localhost")
+            .and_then(|base| base.join(target))
+            .map(|url| url.query_pairs().into_owned().collect())
+            .unwrap_or_default();
+        debug!("Received {} with parameters {:?}", target, query.keys());
+
+        if !["code", "error", "state"]
+            .iter()
+            .any(|key| query.contains_key(*key))
+        {
+            respond(&stream, "404 Not Found", "Not found", "")?;
+            return Ok(None);
+        }
+
+        let result = self.grant(&query, state);
+        if let Err(OauthError::StateMismatch) = result {
+            warn!("Ignoring a redirect with a wrong state");
+            respond(
+                &stream,
+                "400 Bad Request",
+                "Unknown authorization",
+                "This request does not answer the authorization fit-connect-rs is waiting for.",
+            )?;
+            return Ok(None);
+        }
+
+        let written = match &result {
+            Ok(grant) if grant.missing_scopes.is_empty() => respond(
+                &stream,
+                "200 OK",
+                "Authorized",
+                &format!(
+                    "fit-connect-rs is authorized with {}. You can close this window.",
+                    self.service
+                ),
+            ),
+            Ok(grant) => respond(
+                &stream,
+                "200 OK",
+                "Partially authorized",
+                &format!(
+                    "fit-connect-rs is authorized with {}, but without {}. Commands needing \
+                     these permissions will fail until you authorize again and approve them.",
+                    self.service,
+                    grant.missing_scopes.join(", ")
+                ),
+            ),
+            Err(e) => respond(
+                &stream,
+                "400 Bad Request",
+                "Authorization failed",
+                &format!(
+                    "fit-connect-rs could not be authorized with {}: {}",
+                    self.service, e
+                ),
+            ),
+        };
+        if let Err(e) = written {
+            warn!("Failed to answer the authorization redirect: {}", e);
+        }
+
+        Ok(Some(result))
+    }
+
+    /// Checks the parameters of the redirect.
+    ///
+    /// The `state` is checked first, so only the redirect of this request can
+    /// report an error.
+    fn grant(&self, query: &HashMap<String, String>, state: &str) -> Result<Grant, OauthError> {
+        if query.get("state").map(String::as_str) != Some(state) {
+            return Err(OauthError::StateMismatch);
+        }
+        if let Some(error) = query.get("error") {
+            return Err(OauthError::Denied {
+                error: error.clone(),
+            });
+        }
+        let code = query
+            .get("code")
+            .filter(|code| !code.is_empty())
+            .ok_or(OauthError::MissingCode)?;
+
+        // Only some services report the scopes the user approved
+        let missing_scopes = match query.get("scope") {
+            Some(granted) => {
+                let granted: Vec<&str> = granted.split([',', ' ']).collect();
+                self.scope
+                    .split(',')
+                    .filter(|scope| !granted.contains(scope))
+                    .map(str::to_string)
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        if !missing_scopes.is_empty() {
+            warn!("Scopes not granted: {}", missing_scopes.join(", "));
+        }
+
+        Ok(Grant {
+            code: code.clone(),
+            missing_scopes,
+        })
+    }
+}
+
+/// Writes a small HTML page as the response to the browser.
+fn respond(mut stream: &TcpStream, status: &str, title: &str, message: &str) -> io::Result<()> {
+    let body = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{0}</title></head>\
+         <body><h1>{0}</h1><p>{1}</p></body></html>",
+        escape(title),
+        escape(message)
+    );
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Escapes text for an HTML page, as error messages come from the query.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: &str = "s7d8f9g0";
+
+    fn authorization() -> Authorization<'static> {
+        Authorization {
+            service: "Strava",
+            auth_url: "https://www.strava.com/oauth/authorize",
+            client_id: "72920",
+            scope: "read,activity:read_all,activity:write",
+            port: 8888,
+            extra: &[("approval_prompt", "force")],
+        }
+    }
+
+    fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn grants_the_code_of_a_matching_redirect() {
+        let grant = authorization()
+            .grant(&query(&[("state", STATE), ("code", "abc")]), STATE)
+            .unwrap();
+
+        assert_eq!(grant.code, "abc");
+        assert!(grant.missing_scopes.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_wrong_or_missing_state() {
+        let authorization = authorization();
+
+        for pairs in [
+            &[("state", "other"), ("code", "abc")][..],
+            &[("code", "abc")][..],
+            &[("state", ""), ("code", "abc")][..],
+        ] {
+            let result = authorization.grant(&query(pairs), STATE);
+            assert!(matches!(result, Err(OauthError::StateMismatch)));
+        }
+    }
+
+    #[test]
+    fn checks_the_state_before_an_error() {
+        let authorization = authorization();
+
+        let forged = authorization.grant(&query(&[("error", "access_denied")]), STATE);
+        assert!(matches!(forged, Err(OauthError::StateMismatch)));
+
+        let denied = authorization.grant(
+            &query(&[("state", STATE), ("error", "access_denied")]),
+            STATE,
+        );
+        assert!(matches!(denied, Err(OauthError::Denied { error }) if error == "access_denied"));
+    }
+
+    #[test]
+    fn requires_a_code() {
+        let authorization = authorization();
+
+        for pairs in [
+            &[("state", STATE)][..],
+            &[("state", STATE), ("code", "")][..],
+        ] {
+            let result = authorization.grant(&query(pairs), STATE);
+            assert!(matches!(result, Err(OauthError::MissingCode)));
+        }
+    }
+
+    #[test]
+    fn lists_scopes_not_granted_with_commas() {
+        let grant = authorization()
+            .grant(
+                &query(&[
+                    ("state", STATE),
+                    ("code", "abc"),
+                    ("scope", "read,activity:read_all"),
+                ]),
+                STATE,
+            )
+            .unwrap();
+
+        assert_eq!(grant.missing_scopes, ["activity:write"]);
+    }
+
+    #[test]
+    fn lists_scopes_not_granted_with_spaces() {
+        let granted = "activity:write read";
+        let grant = authorization()
+            .grant(
+                &query(&[("state", STATE), ("code", "abc"), ("scope", granted)]),
+                STATE,
+            )
+            .unwrap();
+
+        assert_eq!(grant.missing_scopes, ["activity:read_all"]);
+    }
+
+    #[test]
+    fn every_scope_granted() {
+        let granted = "read,activity:read_all,activity:write";
+        let grant = authorization()
+            .grant(
+                &query(&[("state", STATE), ("code", "abc"), ("scope", granted)]),
+                STATE,
+            )
+            .unwrap();
+
+        assert!(grant.missing_scopes.is_empty());
+    }
+
+    #[test]
+    fn url_carries_the_request() {
+        let url = authorization().url(STATE).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.path(), "/oauth/authorize");
+        assert_eq!(params["client_id"], "72920");
+        assert_eq!(params["redirect_uri"], "http://localhost:8888");
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["scope"], "read,activity:read_all,activity:write");
+        assert_eq!(params["state"], STATE);
+        assert_eq!(params["approval_prompt"], "force");
+    }
+}
@@ -0,0 +1,553 @@
+//! Named profiles for running the tool on behalf of several athletes
+//!
+//! A profile holds the application credentials of each service for one
+//! athlete. Profiles are kept in `profiles.json` in the configuration
+//! directory, and the tokens of each profile in a directory of its own, so
+//! profiles never share tokens with each other or with the environment based
+//! setup. Settings other than the credentials, like the API URLs, come from
+//! the [configuration](super::config).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+#[cfg(any(feature = "strava", feature = "withings"))]
+use super::config::ServiceConfig;
+pub use super::config::{config_dir, HOME_ENV};
+use super::files::write_private;
+#[cfg(feature = "strava")]
+use super::strava::StravaSession;
+#[cfg(feature = "withings")]
+use super::withings::WithingsSession;
+
+/// Errors that can occur while managing profiles
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum ProfileError {
+    /// No profile has the requested name
+    #[error("Profile {name} does not exist")]
+    #[diagnostic(
+        code(profile::not_found),
+        help("List the profiles with `fit-connect-rs profile list`")
+    )]
+    NotFound {
+        /// Name of the profile
+        name: String,
+    },
+
+    /// The name cannot be used for a profile
+    #[error("Invalid profile name {name:?}")]
+    #[diagnostic(
+        code(profile::invalid_name),
+        help("Use letters, digits, '-' and '_' only")
+    )]
+    InvalidName {
+        /// Rejected name
+        name: String,
+    },
+
+    /// The profile already has credentials for a service
+    #[error("Profile {name} already has {service} credentials")]
+    #[diagnostic(
+        code(profile::exists),
+        help("Remove the profile with `fit-connect-rs profile remove {name}` first, as its tokens belong to the old application")
+    )]
+    Exists {
+        /// Name of the profile
+        name: String,
+        /// Service the credentials are set for
+        service: &'static str,
+    },
+
+    /// The profile has no credentials for a service
+    #[error("Profile {name} has no {service} credentials")]
+    #[diagnostic(code(profile::missing_credentials))]
+    MissingCredentials {
+        /// Name of the profile
+        name: String,
+        /// Service the credentials are missing for
+        service: &'static str,
+        /// How to add the credentials
+        #[help]
+        help: String,
+    },
+
+    /// The profiles file or a token directory cannot be read or written
+    #[error("Failed to access {path}")]
+    #[diagnostic(code(profile::io))]
+    Io {
+        /// Path of the file or directory
+        path: PathBuf,
+        /// The underlying error
+        #[source]
+        source: io::Error,
+    },
+
+    /// The profiles file is not valid JSON
+    #[error("The profiles file {path} is invalid")]
+    #[diagnostic(code(profile::parse))]
+    Parse {
+        /// Path of the profiles file
+        path: PathBuf,
+        /// The underlying error
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// Creates a Strava session for a profile, or from the configuration without one.
+///
+/// # Arguments
+///
+/// * `profile` - Profile of the athlete, if any
+/// * `config` - Strava configuration
+///
+/// # Errors
+///
+/// Returns an error if the profile or the configuration has no Strava credentials.
+#[cfg(feature = "strava")]
+pub fn strava_session(
+    profile: Option<&Profile>,
+    config: &ServiceConfig,
+) -> miette::Result<StravaSession> {
+    match profile {
+        Some(profile) => Ok(profile.strava_session(config)?),
+        None => Ok(StravaSession::from_config(config)?),
+    }
+}
+
+/// Creates a Withings session for a profile, or from the configuration without one.
+///
+/// # Arguments
+///
+/// * `profile` - Profile of the athlete, if any
+/// * `config` - Withings configuration
+///
+/// # Errors
+///
+/// Returns an error if the profile or the configuration has no Withings credentials.
+#[cfg(feature = "withings")]
+pub fn withings_session(
+    profile: Option<&Profile>,
+    config: &ServiceConfig,
+) -> miette::Result<WithingsSession> {
+    match profile {
+        Some(profile) => Ok(profile.withings_session(config)?),
+        None => WithingsSession::from_config(config),
+    }
+}
+
+/// Client credentials of an application registered with a service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+    /// Client ID of the application
+    pub client_id: String,
+    /// Client secret of the application
+    pub client_secret: String,
+}
+
+/// Credentials of one athlete for each service
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// Name of the profile
+    #[serde(skip)]
+    pub name: String,
+    /// Strava application credentials, if the profile uses Strava
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strava: Option<Credentials>,
+    /// Withings application credentials, if the profile uses Withings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub withings: Option<Credentials>,
+}
+
+impl Profile {
+    /// Returns the directory holding the tokens of the profile.
+    pub fn token_dir(&self) -> PathBuf {
+        config_dir().join("profiles").join(&self.name)
+    }
+
+    /// Returns the names of the services the profile has credentials for.
+    pub fn services(&self) -> Vec<&'static str> {
+        let mut services = Vec::new();
+        if self.strava.is_some() {
+            services.push("strava");
+        }
+        if self.withings.is_some() {
+            services.push("withings");
+        }
+        services
+    }
+
+    /// Creates a Strava session with the credentials and tokens of the profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Strava configuration, whose credentials and token file are
+    ///   replaced by those of the profile
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProfileError::MissingCredentials` if the profile has no Strava
+    /// credentials.
+    #[cfg(feature = "strava")]
+    pub fn strava_session(&self, config: &ServiceConfig) -> Result<StravaSession, ProfileError> {
+        let credentials = self.credentials(&self.strava, "strava")?;
+        Ok(
+            StravaSession::new(&credentials.client_id, &credentials.client_secret)
+                .with_config(config)
+                .with_token_file(self.token_dir().join("strava.json")),
+        )
+    }
+
+    /// Creates a Withings session with the credentials and tokens of the profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Withings configuration, whose credentials and token file
+    ///   are replaced by those of the profile
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProfileError::MissingCredentials` if the profile has no
+    /// Withings credentials.
+    #[cfg(feature = "withings")]
+    pub fn withings_session(
+        &self,
+        config: &ServiceConfig,
+    ) -> Result<WithingsSession, ProfileError> {
+        let credentials = self.credentials(&self.withings, "withings")?;
+        Ok(
+            WithingsSession::new(&credentials.client_id, &credentials.client_secret)
+                .with_config(config)
+                .with_token_file(self.token_dir().join("withings.json")),
+        )
+    }
+
+    /// Returns the credentials of a service or the error naming what is missing.
+    #[cfg(any(feature = "strava", feature = "withings"))]
+    fn credentials<'a>(
+        &self,
+        credentials: &'a Option<Credentials>,
+        service: &'static str,
+    ) -> Result<&'a Credentials, ProfileError> {
+        credentials
+            .as_ref()
+            .ok_or_else(|| ProfileError::MissingCredentials {
+                name: self.name.clone(),
+                service,
+                help: format!(
+                    "Add them with `fit-connect-rs profile add {} --{}-client-id ..`",
+                    self.name, service
+                ),
+            })
+    }
+}
+
+/// The profiles saved in a profiles file
+///
+/// # Examples
+///
+/// ```no_run
+/// use fit_connect_rs::modules::config::Config;
+/// use fit_connect_rs::modules::profile::Profiles;
+///
+/// let config = Config::load(None)?;
+/// let profiles = Profiles::load()?;
+/// let session = profiles.get("alice")?.strava_session(&config.strava)?;
+/// let athlete = session.get_authenticated_athlete()?;
+/// # Ok::<(), miette::Report>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Profiles {
+    /// Path of the profiles file
+    path: PathBuf,
+    /// Profiles by name
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    /// Loads the profiles of the configuration directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ProfileError` if the profiles file exists but cannot be read.
+    pub fn load() -> Result<Self, ProfileError> {
+        Profiles::load_from(&config_dir().join("profiles.json"))
+    }
+
+    /// Loads the profiles of a profiles file, or none if it does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the profiles file
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ProfileError` if the file exists but cannot be read or parsed.
+    pub fn load_from(path: &Path) -> Result<Self, ProfileError> {
+        let mut profiles: BTreeMap<String, Profile> = match fs::read(path) {
+            Ok(contents) => {
+                serde_json::from_slice(&contents).map_err(|source| ProfileError::Parse {
+                    path: path.to_path_buf(),
+                    source,
+                })?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(source) => {
+                return Err(ProfileError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+
+        for (name, profile) in profiles.iter_mut() {
+            profile.name = name.clone();
+        }
+
+        Ok(Profiles {
+            path: path.to_path_buf(),
+            profiles,
+        })
+    }
+
+    /// Writes the profiles back to their file, readable by its owner only.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProfileError::Io` if the file cannot be written.
+    pub fn save(&self) -> Result<(), ProfileError> {
+        let contents =
+            serde_json::to_vec_pretty(&self.profiles).map_err(|source| ProfileError::Parse {
+                path: self.path.clone(),
+                source,
+            })?;
+
+        write_private(&self.path, contents).map_err(|source| ProfileError::Io {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// Returns the profiles in name order.
+    pub fn iter(&self) -> impl Iterator<Item = &Profile> {
+        self.profiles.values()
+    }
+
+    /// Returns a profile by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProfileError::NotFound` if no profile has the name.
+    pub fn get(&self, name: &str) -> Result<&Profile, ProfileError> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| ProfileError::NotFound {
+                name: name.to_string(),
+            })
+    }
+
+    /// Adds a profile, or the credentials of another service to an existing one.
+    ///
+    /// Credentials that are not given are left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the profile
+    /// * `strava` - Strava application credentials
+    /// * `withings` - Withings application credentials
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProfileError::InvalidName` if the name cannot be used as a
+    /// directory name, or `ProfileError::Exists` if the profile already has
+    /// credentials for one of the services.
+    pub fn add(
+        &mut self,
+        name: &str,
+        strava: Option<Credentials>,
+        withings: Option<Credentials>,
+    ) -> Result<&Profile, ProfileError> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ProfileError::InvalidName {
+                name: name.to_string(),
+            });
+        }
+
+        if let Some(profile) = self.profiles.get(name) {
+            let duplicate = [
+                ("strava", profile.strava.is_some() && strava.is_some()),
+                ("withings", profile.withings.is_some() && withings.is_some()),
+            ];
+            if let Some((service, _)) = duplicate.into_iter().find(|(_, duplicate)| *duplicate) {
+                return Err(ProfileError::Exists {
+                    name: name.to_string(),
+                    service,
+                });
+            }
+        }
+
+        let profile = self
+            .profiles
+            .entry(name.to_string())
+            .or_insert_with(|| Profile {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        if strava.is_some() {
+            profile.strava = strava;
+        }
+        if withings.is_some() {
+            profile.withings = withings;
+        }
+
+        Ok(profile)
+    }
+
+    /// Removes a profile and deletes its tokens.
+    ///
+    /// The profiles file is saved before the tokens are deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProfileError::NotFound` if no profile has the name, or
+    /// `ProfileError::Io` if the file cannot be saved or the tokens deleted.
+    pub fn remove(&mut self, name: &str) -> Result<Profile, ProfileError> {
+        let profile = self
+            .profiles
+            .remove(name)
+            .ok_or_else(|| ProfileError::NotFound {
+                name: name.to_string(),
+            })?;
+        self.save()?;
+
+        let token_dir = profile.token_dir();
+        match fs::remove_dir_all(&token_dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(ProfileError::Io {
+                path: token_dir,
+                source: e,
+            }),
+            _ => Ok(profile),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::tests::with_env;
+    use super::*;
+
+    fn credentials(client_id: &str) -> Option<Credentials> {
+        Some(Credentials {
+            client_id: client_id.to_string(),
+            client_secret: format!("{}-secret", client_id),
+        })
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut profiles = Profiles::default();
+
+        for name in ["", "../alice", "alice bob", "alice/strava", "é"] {
+            let result = profiles.add(name, credentials("1"), None);
+            assert!(
+                matches!(result, Err(ProfileError::InvalidName { .. })),
+                "{:?}",
+                name
+            );
+        }
+        assert!(profiles.add("alice-2_b", credentials("1"), None).is_ok());
+    }
+
+    #[test]
+    fn duplicate_credentials_are_rejected() {
+        let mut profiles = Profiles::default();
+        profiles.add("alice", credentials("1"), None).unwrap();
+
+        let result = profiles.add("alice", credentials("2"), None);
+        assert!(matches!(
+            result,
+            Err(ProfileError::Exists {
+                service: "strava",
+                ..
+            })
+        ));
+        assert_eq!(profiles.get("alice").unwrap().strava, credentials("1"));
+
+        let profile = profiles.add("alice", None, credentials("3")).unwrap();
+        assert_eq!(profile.services(), ["strava", "withings"]);
+    }
+
+    #[test]
+    fn profiles_round_trip() {
+        with_env("profiles-round-trip", &[], |dir| {
+            let mut profiles = Profiles::load().unwrap();
+            assert_eq!(profiles.iter().count(), 0);
+            profiles.add("alice", credentials("1"), None).unwrap();
+            profiles
+                .add("bob", credentials("2"), credentials("3"))
+                .unwrap();
+            profiles.save().unwrap();
+
+            let loaded = Profiles::load_from(&dir.join("profiles.json")).unwrap();
+            let names: Vec<_> = loaded.iter().map(|profile| profile.name.as_str()).collect();
+            assert_eq!(names, ["alice", "bob"]);
+            let bob = loaded.get("bob").unwrap();
+            assert_eq!(bob.strava, credentials("2"));
+            assert_eq!(bob.withings, credentials("3"));
+            assert_eq!(bob.token_dir(), dir.join("profiles").join("bob"));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn profiles_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        with_env("profiles-private", &[], |dir| {
+            let mut profiles = Profiles::load().unwrap();
+            profiles.add("alice", credentials("1"), None).unwrap();
+            profiles.save().unwrap();
+
+            let mode = fs::metadata(dir.join("profiles.json"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        });
+    }
+
+    #[test]
+    fn remove_deletes_the_tokens() {
+        with_env("profiles-remove", &[], |dir| {
+            let mut profiles = Profiles::load().unwrap();
+            profiles.add("alice", credentials("1"), None).unwrap();
+            profiles.add("bob", credentials("2"), None).unwrap();
+            profiles.save().unwrap();
+            for name in ["alice", "bob"] {
+                let token_dir = profiles.get(name).unwrap().token_dir();
+                fs::create_dir_all(&token_dir).unwrap();
+                fs::write(token_dir.join("strava.json"), "{}").unwrap();
+            }
+
+            let removed = profiles.remove("alice").unwrap();
+            assert_eq!(removed.name, "alice");
+            assert!(!removed.token_dir().exists());
+            assert!(profiles.get("bob").unwrap().token_dir().exists());
+
+            let loaded = Profiles::load_from(&dir.join("profiles.json")).unwrap();
+            assert!(matches!(
+                loaded.get("alice"),
+                Err(ProfileError::NotFound { .. })
+            ));
+            assert!(matches!(
+                profiles.remove("alice"),
+                Err(ProfileError::NotFound { .. })
+            ));
+        });
+    }
+}
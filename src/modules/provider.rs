@@ -0,0 +1,352 @@
+//! Provider abstraction shared by the fitness service modules.
+//!
+//! Every service exposes what it can produce or accept through the traits in
+//! this module, so any source can be routed to any sink without the CLI
+//! knowing which services are involved.
+
+use miette::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::modules::weight::{WeightPolicy, WeightReading};
+use crate::modules::workout::{CreatedActivity, Workout, WorkoutSyncReport};
+
+use crate::modules::config::Config;
+#[cfg(feature = "strava")]
+use crate::modules::profile::strava_session;
+#[cfg(feature = "withings")]
+use crate::modules::profile::withings_session;
+use crate::modules::profile::Profile;
+
+/// Errors raised by the provider layer itself rather than a service
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum ProviderError {
+    /// The source returned no weight for the requested period
+    #[error("No weight measurements available from {source_name}")]
+    #[diagnostic(
+        code(provider::weight::empty),
+        help("Weigh in or increase the number of days to look back")
+    )]
+    NoWeight {
+        /// Name of the source that was queried
+        source_name: &'static str,
+    },
+}
+
+/// A service that can produce body measurements.
+pub trait MeasurementSource {
+    /// Human readable name of the service, used in log and status messages
+    fn name(&self) -> &'static str;
+
+    /// Retrieves every weight reading taken after `since`.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Unix timestamp after which measurements are considered
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service cannot be reached.
+    fn weights(&self, since: i64) -> Result<Vec<WeightReading>>;
+
+    /// Retrieves the weight, in kilograms, selected by `policy` from the readings
+    /// taken after `since`.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Unix timestamp after which measurements are considered
+    /// * `policy` - How to pick one weight from several readings
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service cannot be reached or has no measurement
+    /// for the requested period.
+    fn weight(&self, since: i64, policy: WeightPolicy) -> Result<f64> {
+        policy
+            .select(&self.weights(since)?)
+            .ok_or_else(|| ProviderError::NoWeight {
+                source_name: self.name(),
+            })
+            .map_err(Into::into)
+    }
+
+    /// Retrieves the most recent weight, in kilograms, recorded after `since`.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Unix timestamp after which measurements are considered
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service cannot be reached or has no measurement
+    /// for the requested period.
+    fn latest_weight(&self, since: i64) -> Result<f64> {
+        self.weight(since, WeightPolicy::Latest)
+    }
+}
+
+/// A service that keeps an athlete profile which can be updated.
+pub trait ProfileSink {
+    /// Human readable name of the service, used in log and status messages
+    fn name(&self) -> &'static str;
+
+    /// Updates the weight stored in the athlete profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `weight_kg` - The athlete's weight in kilograms
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service rejects the update.
+    fn update_weight(&self, weight_kg: f64) -> Result<()>;
+}
+
+/// A service that records workouts.
+pub trait WorkoutSource {
+    /// Human readable name of the service, used in log and status messages
+    fn name(&self) -> &'static str;
+
+    /// Retrieves every workout started after `since`, in chronological order.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Unix timestamp after which workouts are considered
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service cannot be reached.
+    fn workouts(&self, since: i64) -> Result<Vec<Workout>>;
+}
+
+/// A service that keeps an activity log which can be added to.
+pub trait ActivitySink {
+    /// Human readable name of the service, used in log and status messages
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the log already has an activity for the workout.
+    ///
+    /// # Arguments
+    ///
+    /// * `workout` - The workout to look for
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service cannot be reached.
+    fn has_activity(&self, workout: &Workout) -> Result<bool>;
+
+    /// Creates an activity for the workout and returns its identifier.
+    ///
+    /// # Arguments
+    ///
+    /// * `workout` - The workout to create an activity for
+    /// * `origin` - Name of the service the workout was recorded by
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service rejects the activity.
+    fn create_activity(&self, workout: &Workout, origin: &str) -> Result<i64>;
+}
+
+/// Services that can be used as a measurement source.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// Withings scales and health devices
+    #[cfg(feature = "withings")]
+    Withings,
+}
+
+/// Services that can be used as a profile sink.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+    /// Strava athlete profile
+    #[cfg(feature = "strava")]
+    Strava,
+}
+
+impl SourceKind {
+    /// Creates a session for this source from a profile, or from the
+    /// configuration without one.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Profile of the athlete, if any
+    /// * `config` - Configuration providing the URLs and default credentials
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service credentials are not configured.
+    #[cfg_attr(not(feature = "withings"), allow(unused_variables))]
+    pub fn source(
+        self,
+        profile: Option<&Profile>,
+        config: &Config,
+    ) -> Result<Box<dyn MeasurementSource>> {
+        match self {
+            #[cfg(feature = "withings")]
+            SourceKind::Withings => Ok(Box::new(withings_session(profile, &config.withings)?)),
+        }
+    }
+
+    /// Creates a session for this source as a workout source from a profile,
+    /// or from the configuration without one.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Profile of the athlete, if any
+    /// * `config` - Configuration providing the URLs and default credentials
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service credentials are not configured.
+    #[cfg_attr(not(feature = "withings"), allow(unused_variables))]
+    pub fn workout_source(
+        self,
+        profile: Option<&Profile>,
+        config: &Config,
+    ) -> Result<Box<dyn WorkoutSource>> {
+        match self {
+            #[cfg(feature = "withings")]
+            SourceKind::Withings => Ok(Box::new(withings_session(profile, &config.withings)?)),
+        }
+    }
+}
+
+impl SinkKind {
+    /// Creates a session for this sink from a profile, or from the
+    /// configuration without one.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Profile of the athlete, if any
+    /// * `config` - Configuration providing the URLs and default credentials
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service credentials are not configured.
+    #[cfg_attr(not(feature = "strava"), allow(unused_variables))]
+    pub fn sink(self, profile: Option<&Profile>, config: &Config) -> Result<Box<dyn ProfileSink>> {
+        match self {
+            #[cfg(feature = "strava")]
+            SinkKind::Strava => Ok(Box::new(strava_session(profile, &config.strava)?)),
+        }
+    }
+
+    /// Creates a session for this sink as an activity sink from a profile,
+    /// or from the configuration without one.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - Profile of the athlete, if any
+    /// * `config` - Configuration providing the URLs and default credentials
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service credentials are not configured.
+    #[cfg_attr(not(feature = "strava"), allow(unused_variables))]
+    pub fn activity_sink(
+        self,
+        profile: Option<&Profile>,
+        config: &Config,
+    ) -> Result<Box<dyn ActivitySink>> {
+        match self {
+            #[cfg(feature = "strava")]
+            SinkKind::Strava => Ok(Box::new(strava_session(profile, &config.strava)?)),
+        }
+    }
+}
+
+/// Weight copied to one profile sink
+#[derive(Debug, Clone, Serialize)]
+pub struct WeightSync {
+    /// Name of the service the weight was written to
+    pub sink: &'static str,
+    /// The weight in kilograms that was synced
+    pub weight_kg: f64,
+}
+
+/// Copies the weight selected by `policy` from a measurement source to a profile sink.
+///
+/// # Arguments
+///
+/// * `source` - Service the weight is read from
+/// * `sink` - Service the weight is written to
+/// * `since` - Unix timestamp after which measurements are considered
+/// * `policy` - How to pick one weight from several readings
+///
+/// # Returns
+///
+/// The sink and the weight in kilograms that was synced.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The source has no measurement for the period
+/// - The sink rejects the update
+pub fn sync_weight(
+    source: &dyn MeasurementSource,
+    sink: &dyn ProfileSink,
+    since: i64,
+    policy: WeightPolicy,
+) -> Result<WeightSync> {
+    let weight = source
+        .weight(since, policy)
+        .wrap_err_with(|| format!("Failed to get weight from {}", source.name()))?;
+
+    sink.update_weight(weight)
+        .wrap_err_with(|| format!("Failed to sync weight with {}", sink.name()))?;
+
+    Ok(WeightSync {
+        sink: sink.name(),
+        weight_kg: weight,
+    })
+}
+
+/// Recreates the workouts of a source as activities in a sink, skipping the
+/// workouts the sink already has an activity for.
+///
+/// # Arguments
+///
+/// * `source` - Service the workouts are read from
+/// * `sink` - Service the activities are created in
+/// * `since` - Unix timestamp after which workouts are considered
+///
+/// # Returns
+///
+/// The activities created and the workouts skipped.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The workouts cannot be retrieved from the source
+/// - The sink cannot be queried or rejects an activity
+pub fn sync_workouts(
+    source: &dyn WorkoutSource,
+    sink: &dyn ActivitySink,
+    since: i64,
+) -> Result<WorkoutSyncReport> {
+    let workouts = source
+        .workouts(since)
+        .wrap_err_with(|| format!("Failed to get workouts from {}", source.name()))?;
+
+    let mut report = WorkoutSyncReport {
+        sink: sink.name(),
+        ..Default::default()
+    };
+    for workout in workouts {
+        if sink.has_activity(&workout)? {
+            report.skipped.push(workout);
+            continue;
+        }
+
+        let id = sink
+            .create_activity(&workout, source.name())
+            .wrap_err_with(|| format!("Failed to create activity in {}", sink.name()))?;
+        report.created.push(CreatedActivity { id, workout });
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,716 @@
+//! Strava activities
+//!
+//! Lists, retrieves, creates and updates activities of the authenticated athlete. Listing is lazy:
+//! [`Activities`] only requests the next page once the previous one is consumed.
+//! Docs: https://developers.strava.com/docs/reference/#api-Activities
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::request::Api;
+use super::segments::Effort;
+use super::streams::Streams;
+use super::StravaError;
+
+/// Number of activities requested per page, the maximum Strava allows
+const PER_PAGE: usize = 200;
+
+/// Summary of an activity as returned by the activity list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activity {
+    /// Identifier of the activity
+    pub id: i64,
+    /// Name of the activity
+    pub name: String,
+    /// Sport type of the activity, e.g. `Run` or `GravelRide`
+    pub sport_type: String,
+    /// Start time of the activity
+    pub start_date: DateTime<Utc>,
+    /// Start time of the activity in the athlete's timezone
+    pub start_date_local: Option<String>,
+    /// Timezone of the activity
+    pub timezone: Option<String>,
+    /// Distance (m)
+    #[serde(default)]
+    pub distance: f64,
+    /// Moving time (s)
+    #[serde(default)]
+    pub moving_time: i64,
+    /// Elapsed time (s)
+    #[serde(default)]
+    pub elapsed_time: i64,
+    /// Elevation gain (m)
+    #[serde(default)]
+    pub total_elevation_gain: f64,
+    /// Average speed (m/s)
+    pub average_speed: Option<f64>,
+    /// Average heart rate (bpm)
+    pub average_heartrate: Option<f64>,
+    /// Maximum heart rate (bpm)
+    pub max_heartrate: Option<f64>,
+    /// Identifier of the gear used
+    pub gear_id: Option<String>,
+    /// Whether the activity is a commute
+    #[serde(default)]
+    pub commute: bool,
+    /// Whether the activity was recorded on a trainer
+    #[serde(default)]
+    pub trainer: bool,
+    /// Whether the activity was created manually
+    #[serde(default)]
+    pub manual: bool,
+    /// Whether the activity is private
+    #[serde(default)]
+    pub private: bool,
+    /// Visibility of the activity, e.g. `everyone` or `only_me`
+    pub visibility: Option<String>,
+    /// Identifier of the file the activity was uploaded from
+    pub external_id: Option<String>,
+}
+
+/// A lap of an activity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lap {
+    /// Identifier of the lap
+    pub id: i64,
+    /// Name of the lap
+    pub name: String,
+    /// Position of the lap in the activity, starting at 1
+    pub lap_index: i64,
+    /// Start time of the lap
+    pub start_date: DateTime<Utc>,
+    /// Elapsed time (s)
+    pub elapsed_time: i64,
+    /// Moving time (s)
+    #[serde(default)]
+    pub moving_time: i64,
+    /// Distance (m)
+    #[serde(default)]
+    pub distance: f64,
+    /// Elevation gain (m)
+    pub total_elevation_gain: Option<f64>,
+    /// Average speed (m/s)
+    pub average_speed: Option<f64>,
+    /// Maximum speed (m/s)
+    pub max_speed: Option<f64>,
+    /// Average heart rate (bpm)
+    pub average_heartrate: Option<f64>,
+    /// Maximum heart rate (bpm)
+    pub max_heartrate: Option<f64>,
+    /// Average cadence (rpm)
+    pub average_cadence: Option<f64>,
+    /// Average power (W)
+    pub average_watts: Option<f64>,
+}
+
+/// A split of an activity over one kilometer or one mile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Split {
+    /// Position of the split in the activity, starting at 1
+    pub split: i64,
+    /// Distance (m)
+    pub distance: f64,
+    /// Elapsed time (s)
+    pub elapsed_time: i64,
+    /// Moving time (s)
+    #[serde(default)]
+    pub moving_time: i64,
+    /// Elevation difference between the end and the start (m)
+    pub elevation_difference: Option<f64>,
+    /// Average speed (m/s)
+    pub average_speed: Option<f64>,
+    /// Average heart rate (bpm)
+    pub average_heartrate: Option<f64>,
+    /// Pace zone
+    pub pace_zone: Option<i64>,
+}
+
+/// Full representation of an activity
+///
+/// Only the owner of the activity gets the laps, splits and efforts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetailedActivity {
+    /// Fields shared with the activity list
+    #[serde(flatten)]
+    pub summary: Activity,
+    /// Description of the activity
+    pub description: Option<String>,
+    /// Calories burned (kcal)
+    pub calories: Option<f64>,
+    /// Whether the activity is hidden from the home feed
+    #[serde(default)]
+    pub hide_from_home: bool,
+    /// Name of the device that recorded the activity
+    pub device_name: Option<String>,
+    /// Laps of the activity
+    #[serde(default)]
+    pub laps: Vec<Lap>,
+    /// Splits over every kilometer
+    #[serde(default)]
+    pub splits_metric: Vec<Split>,
+    /// Splits over every mile
+    #[serde(default)]
+    pub splits_standard: Vec<Split>,
+    /// Best efforts over standard distances, e.g. `5k`
+    #[serde(default)]
+    pub best_efforts: Vec<Effort>,
+    /// Efforts on the segments the activity went through
+    #[serde(default)]
+    pub segment_efforts: Vec<Effort>,
+    /// Streams of the activity, when requested
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub streams: Option<Streams>,
+}
+
+impl From<Activity> for DetailedActivity {
+    /// Wraps an activity of the list, without laps, splits, efforts or description.
+    fn from(summary: Activity) -> Self {
+        DetailedActivity {
+            summary,
+            description: None,
+            calories: None,
+            hide_from_home: false,
+            device_name: None,
+            laps: Vec::new(),
+            splits_metric: Vec::new(),
+            splits_standard: Vec::new(),
+            best_efforts: Vec::new(),
+            segment_efforts: Vec::new(),
+            streams: None,
+        }
+    }
+}
+
+/// A manual activity to create
+#[derive(Debug, Clone)]
+pub struct NewActivity {
+    /// Name of the activity
+    pub name: String,
+    /// Sport type of the activity, e.g. `Run`
+    pub sport_type: String,
+    /// Start time in the athlete's timezone
+    pub start_date_local: NaiveDateTime,
+    /// Elapsed time (s)
+    pub elapsed_time: i64,
+    /// Description of the activity
+    pub description: Option<String>,
+    /// Distance (m)
+    pub distance: Option<f64>,
+    /// Whether the activity was done on a trainer
+    pub trainer: bool,
+    /// Whether the activity is a commute
+    pub commute: bool,
+}
+
+/// Who can see an activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    /// Everyone
+    Everyone,
+    /// Followers of the athlete only
+    FollowersOnly,
+    /// The athlete only
+    OnlyMe,
+}
+
+/// Changes to apply to an existing activity
+///
+/// Fields left to `None` are not changed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ActivityUpdate {
+    /// New name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// New description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// New sport type, e.g. `Run`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sport_type: Option<String>,
+    /// Identifier of the gear used, or `none` to remove the gear
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gear_id: Option<String>,
+    /// Whether the activity is a commute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commute: Option<bool>,
+    /// Whether the activity was done on a trainer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trainer: Option<bool>,
+    /// Whether the activity is hidden from the home feed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hide_from_home: Option<bool>,
+    /// Who can see the activity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+}
+
+impl Visibility {
+    /// Returns the value of the visibility in the API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Everyone => "everyone",
+            Visibility::FollowersOnly => "followers_only",
+            Visibility::OnlyMe => "only_me",
+        }
+    }
+}
+
+/// A change of one field of an activity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    /// Name of the field, e.g. `gear_id`
+    pub field: &'static str,
+    /// Current value
+    pub before: String,
+    /// Value after the update
+    pub after: String,
+}
+
+/// The changes an update makes to one activity
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedEdit {
+    /// Identifier of the activity
+    pub id: i64,
+    /// Current name of the activity
+    pub name: String,
+    /// Start time of the activity
+    pub start_date: DateTime<Utc>,
+    /// Fields the update changes
+    pub changes: Vec<FieldChange>,
+}
+
+impl ActivityUpdate {
+    /// Returns whether the update changes nothing.
+    pub fn is_empty(&self) -> bool {
+        *self == ActivityUpdate::default()
+    }
+
+    /// Returns whether the update touches fields missing from the activity list,
+    /// so the full activity is needed to compare against.
+    pub fn needs_detail(&self) -> bool {
+        self.description.is_some() || self.hide_from_home.is_some()
+    }
+
+    /// Returns the fields the update would change on an activity.
+    ///
+    /// Fields already set to the requested value are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `activity` - The activity to compare against
+    pub fn changes(&self, activity: &DetailedActivity) -> Vec<FieldChange> {
+        let current = &activity.summary;
+        let mut changes = Vec::new();
+        let mut compare = |field: &'static str, before: String, after: Option<String>| {
+            if let Some(after) = after.filter(|after| *after != before) {
+                changes.push(FieldChange {
+                    field,
+                    before,
+                    after,
+                });
+            }
+        };
+
+        compare("name", current.name.clone(), self.name.clone());
+        compare(
+            "description",
+            activity.description.clone().unwrap_or_default(),
+            self.description.clone(),
+        );
+        compare(
+            "sport_type",
+            current.sport_type.clone(),
+            self.sport_type.clone(),
+        );
+        compare(
+            "gear_id",
+            current
+                .gear_id
+                .clone()
+                .unwrap_or_else(|| "none".to_string()),
+            self.gear_id.clone(),
+        );
+        compare(
+            "commute",
+            current.commute.to_string(),
+            self.commute.map(|v| v.to_string()),
+        );
+        compare(
+            "trainer",
+            current.trainer.to_string(),
+            self.trainer.map(|v| v.to_string()),
+        );
+        compare(
+            "hide_from_home",
+            activity.hide_from_home.to_string(),
+            self.hide_from_home.map(|v| v.to_string()),
+        );
+        compare(
+            "visibility",
+            current
+                .visibility
+                .clone()
+                .unwrap_or_else(|| "(none)".to_string()),
+            self.visibility.map(|v| v.as_str().to_string()),
+        );
+
+        changes
+    }
+}
+
+impl NewActivity {
+    /// Converts the activity into the form parameters of the create endpoint.
+    fn to_form(&self) -> Vec<(&'static str, String)> {
+        let mut form = vec![
+            ("name", self.name.clone()),
+            ("sport_type", self.sport_type.clone()),
+            (
+                "start_date_local",
+                self.start_date_local
+                    .format("%Y-%m-%dT%H:%M:%S")
+                    .to_string(),
+            ),
+            ("elapsed_time", self.elapsed_time.to_string()),
+            ("trainer", u8::from(self.trainer).to_string()),
+            ("commute", u8::from(self.commute).to_string()),
+        ];
+        if let Some(description) = &self.description {
+            form.push(("description", description.clone()));
+        }
+        if let Some(distance) = self.distance {
+            form.push(("distance", distance.to_string()));
+        }
+        form
+    }
+}
+
+/// Criteria an activity must meet to be listed
+///
+/// Strava only filters on the start time, the other criteria are applied to
+/// each page as it is received.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityFilter {
+    /// Only activities started after this Unix timestamp
+    pub after: Option<i64>,
+    /// Only activities started before this Unix timestamp
+    pub before: Option<i64>,
+    /// Only activities of one of these sport types, e.g. `Run`, compared case-insensitively.
+    /// Empty means every sport type.
+    pub sport_types: Vec<String>,
+    /// Only activities at least this long (m)
+    pub min_distance: Option<f64>,
+    /// Only activities at most this long (m)
+    pub max_distance: Option<f64>,
+    /// Only activities whose name matches this pattern, where `*` matches any
+    /// text and `?` any single character, compared case-insensitively
+    pub name_pattern: Option<String>,
+}
+
+impl ActivityFilter {
+    /// Returns whether an activity meets the sport type, distance and name criteria.
+    pub fn matches(&self, activity: &Activity) -> bool {
+        let sport_matches = self.sport_types.is_empty()
+            || self
+                .sport_types
+                .iter()
+                .any(|sport_type| sport_type.eq_ignore_ascii_case(&activity.sport_type));
+        let distance_matches = self
+            .min_distance
+            .is_none_or(|min_distance| activity.distance >= min_distance)
+            && self
+                .max_distance
+                .is_none_or(|max_distance| activity.distance <= max_distance);
+        let name_matches = self
+            .name_pattern
+            .as_deref()
+            .is_none_or(|pattern| wildcard_match(pattern, &activity.name));
+
+        sport_matches && distance_matches && name_matches
+    }
+}
+
+/// Returns whether a text matches a pattern where `*` matches any text and
+/// `?` any single character, ignoring case.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    // Position of the last `*` in the pattern and of the text it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` absorb one more character
+            backtrack = Some((star, matched + 1));
+            p = star + 1;
+            t = matched + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Lazy iterator over the activities of the authenticated athlete.
+///
+/// A page is requested only when the previous one has been consumed, so any
+/// number of activities can be processed with a single page in memory. The
+/// iterator ends after the first error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fit_connect_rs::modules::strava::ActivityFilter;
+/// use fit_connect_rs::StravaSession;
+///
+/// let session = StravaSession::from_env()?;
+/// let filter = ActivityFilter {
+///     sport_types: vec!["Run".to_string()],
+///     min_distance: Some(10_000.0),
+///     ..Default::default()
+/// };
+/// for activity in session.activities(filter)? {
+///     let activity = activity?;
+///     println!("{} {}", activity.start_date, activity.name);
+/// }
+/// # Ok::<(), miette::Report>(())
+/// ```
+pub struct Activities {
+    /// Connection to the Strava API
+    api: Api,
+    /// Criteria the listed activities must meet
+    filter: ActivityFilter,
+    /// Next page to request
+    page: usize,
+    /// Activities of the current page not yet returned
+    buffer: std::vec::IntoIter<Activity>,
+    /// Whether the last page has been requested
+    done: bool,
+}
+
+impl Activities {
+    /// Creates an iterator that has not requested any page yet.
+    pub(crate) fn new(api: Api, filter: ActivityFilter) -> Self {
+        Activities {
+            api,
+            filter,
+            page: 1,
+            buffer: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+
+    /// Requests the next page of activities.
+    fn fetch_page(&mut self) -> Result<Vec<Activity>, StravaError> {
+        let mut query = vec![
+            ("page", self.page.to_string()),
+            ("per_page", PER_PAGE.to_string()),
+        ];
+        if let Some(after) = self.filter.after {
+            query.push(("after", after.to_string()));
+        }
+        if let Some(before) = self.filter.before {
+            query.push(("before", before.to_string()));
+        }
+
+        self.page += 1;
+        self.api.get("athlete/activities", &query)
+    }
+}
+
+impl Iterator for Activities {
+    type Item = Result<Activity, StravaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(activity) = self.buffer.next() {
+                if self.filter.matches(&activity) {
+                    return Some(Ok(activity));
+                }
+                continue;
+            }
+
+            if self.done {
+                return None;
+            }
+
+            match self.fetch_page() {
+                Ok(batch) => {
+                    self.done = batch.len() < PER_PAGE;
+                    self.buffer = batch.into_iter();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Retrieves the full representation of an activity, including every segment effort.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the activity
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if the activity does not exist or cannot be parsed.
+pub(crate) fn get(api: &Api, id: i64) -> Result<DetailedActivity, StravaError> {
+    let query = [("include_all_efforts", "true".to_string())];
+    api.get(&format!("activities/{}", id), &query)
+}
+
+/// Updates an activity.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the activity
+/// * `update` - Changes to apply
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if Strava rejects the update.
+pub(crate) fn update(
+    api: &Api,
+    id: i64,
+    update: &ActivityUpdate,
+) -> Result<DetailedActivity, StravaError> {
+    api.put(&format!("activities/{}", id), update)
+}
+
+/// Creates a manual activity.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `activity` - The activity to create
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if Strava rejects the activity.
+pub(crate) fn create(api: &Api, activity: &NewActivity) -> Result<Activity, StravaError> {
+    api.post("activities", &activity.to_form())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(visibility: Option<&str>) -> DetailedActivity {
+        serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "name": "Morning Run",
+            "sport_type": "Run",
+            "start_date": "2024-03-01T07:30:00Z",
+            "start_date_local": null,
+            "timezone": null,
+            "average_speed": null,
+            "average_heartrate": null,
+            "max_heartrate": null,
+            "gear_id": null,
+            "commute": true,
+            "visibility": visibility,
+            "external_id": null,
+            "description": null,
+            "calories": null,
+            "device_name": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn star_matches_any_text() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("*", "Morning Run"));
+        assert!(wildcard_match("Morning*", "Morning Run"));
+        assert!(wildcard_match("*Run", "Morning Run"));
+        assert!(wildcard_match("M*g*n", "Morning Run"));
+        assert!(wildcard_match("**Run", "Morning Run"));
+        assert!(wildcard_match("*a*b", "aaab"));
+        assert!(!wildcard_match("*a*b", "aaba"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(wildcard_match("R?n", "Run"));
+        assert!(wildcard_match("???", "Run"));
+        assert!(!wildcard_match("R?n", "Rn"));
+        assert!(!wildcard_match("R?n", "Ruun"));
+        assert!(wildcard_match("*?", "R"));
+        assert!(!wildcard_match("*?", ""));
+    }
+
+    #[test]
+    fn matches_the_whole_name() {
+        assert!(!wildcard_match("Run", "Morning Run"));
+        assert!(!wildcard_match("Morning", "Morning Run"));
+        assert!(wildcard_match("morning run", "Morning Run"));
+        assert!(wildcard_match("AFTERNOON*", "afternoon ride"));
+    }
+
+    #[test]
+    fn empty_pattern_matches_only_an_empty_name() {
+        assert!(wildcard_match("", ""));
+        assert!(!wildcard_match("", "Run"));
+    }
+
+    #[test]
+    fn changes_skip_unchanged_fields() {
+        let update = ActivityUpdate {
+            name: Some("Morning Run".to_string()),
+            sport_type: Some("Run".to_string()),
+            commute: Some(true),
+            gear_id: Some("none".to_string()),
+            trainer: Some(true),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            update.changes(&activity(Some("everyone"))),
+            [FieldChange {
+                field: "trainer",
+                before: "false".to_string(),
+                after: "true".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn changes_show_a_missing_visibility() {
+        let update = ActivityUpdate {
+            visibility: Some(Visibility::FollowersOnly),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            update.changes(&activity(None)),
+            [FieldChange {
+                field: "visibility",
+                before: "(none)".to_string(),
+                after: "followers_only".to_string(),
+            }]
+        );
+        assert!(update.changes(&activity(Some("followers_only"))).is_empty());
+    }
+
+    #[test]
+    fn empty_update_changes_nothing() {
+        assert!(ActivityUpdate::default().is_empty());
+        assert!(ActivityUpdate::default()
+            .changes(&activity(None))
+            .is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+//! Local backup of a Strava account
+//!
+//! Mirrors the athlete profile, stats, gear, routes and every activity with
+//! its streams into a directory:
+//!
+//! ```text
+//! <dir>/manifest.json                  what has been backed up so far
+//! <dir>/athlete.json
+//! <dir>/stats.json
+//! <dir>/gear/<id>.json
+//! <dir>/routes/<id>.json and .gpx
+//! <dir>/activities/<id>/activity.json
+//! <dir>/activities/<id>/streams.json
+//! <dir>/activities/<id>/track.gpx      for activities with a position
+//! ```
+//!
+//! The manifest records a fingerprint of every activity and the update time
+//! of every route. A run only fetches what is new or changed since the
+//! fingerprint was taken, and saves the manifest after each activity, so an
+//! interrupted run resumes where it stopped. Original FIT files are not
+//! available through the API; the GPX tracks are rebuilt from the streams.
+
+use chrono::{DateTime, Utc};
+use log::info;
+use miette::{Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::streams::{self, StreamType};
+use super::{Activity, ActivityFilter, RouteFormat, StravaSession};
+use crate::modules::files::write_atomic;
+
+/// Name of the manifest file
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// What has been backed up so far
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Fingerprint of every backed up activity by identifier
+    #[serde(default)]
+    activities: BTreeMap<i64, String>,
+    /// Last update time of every backed up route by identifier
+    #[serde(default)]
+    routes: BTreeMap<String, Option<DateTime<Utc>>>,
+    /// End of the last complete run
+    completed_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of a backup run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackupReport {
+    /// Number of activities fetched because they were new or changed
+    pub activities_fetched: usize,
+    /// Number of activities already up to date
+    pub activities_unchanged: usize,
+    /// Number of routes exported because they were new or changed
+    pub routes_exported: usize,
+    /// Items that could not be backed up, with the reason; a new run retries them
+    pub failures: Vec<String>,
+}
+
+/// Returns a fingerprint of the fields of an activity the athlete can change.
+///
+/// Uses FNV-1a, which is stable across builds unlike the standard library hasher.
+fn fingerprint(activity: &Activity) -> Result<String> {
+    let json = serde_json::to_vec(activity).into_diagnostic()?;
+    let hash = json.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+
+    Ok(format!("{:016x}", hash))
+}
+
+/// Serializes a value as pretty JSON and writes it atomically.
+fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    let json = serde_json::to_vec_pretty(value).into_diagnostic()?;
+    write_atomic(path, json)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
+
+impl Manifest {
+    /// Reads the manifest of a backup directory, or an empty one for a new backup.
+    fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+
+        let contents = fs::read(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&contents)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Invalid backup manifest {}", path.display()))
+    }
+
+    /// Writes the manifest to a backup directory.
+    fn save(&self, dir: &Path) -> Result<()> {
+        write_json(&dir.join(MANIFEST_FILE), self)
+    }
+}
+
+impl StravaSession {
+    /// Mirrors the account to a directory, fetching only what changed since the
+    /// previous run.
+    ///
+    /// See the [module documentation](crate::modules::strava::backup) for the layout. Items that fail are
+    /// reported and retried by the next run rather than stopping the backup.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Backup directory, created if missing
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The activity list cannot be retrieved
+    /// - The directory or the manifest cannot be written
+    pub fn backup(&self, dir: &Path) -> Result<BackupReport> {
+        fs::create_dir_all(dir)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+        let mut manifest = Manifest::load(dir)?;
+        let mut report = BackupReport::default();
+
+        self.backup_profile(dir, &mut report)?;
+        self.backup_routes(dir, &mut manifest, &mut report)?;
+        self.backup_activities(dir, &mut manifest, &mut report)?;
+
+        manifest.completed_at = Some(Utc::now());
+        manifest.save(dir)?;
+        Ok(report)
+    }
+
+    /// Writes the athlete profile, stats and gear details.
+    fn backup_profile(&self, dir: &Path, report: &mut BackupReport) -> Result<()> {
+        let athlete = self.get_authenticated_athlete()?;
+        write_json(&dir.join("athlete.json"), &athlete)?;
+        write_json(&dir.join("stats.json"), &self.get_athlete_stats()?)?;
+
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+        let gear = athlete.shoes.iter().chain(&athlete.bikes).flatten();
+        for item in gear {
+            match api.get::<Value>(&format!("gear/{}", item.id), &[]) {
+                Ok(details) => write_json(
+                    &dir.join("gear").join(format!("{}.json", item.id)),
+                    &details,
+                )?,
+                Err(e) => report.failures.push(format!("gear {}: {}", item.id, e)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes every new or changed route as JSON and GPX.
+    fn backup_routes(
+        &self,
+        dir: &Path,
+        manifest: &mut Manifest,
+        report: &mut BackupReport,
+    ) -> Result<()> {
+        let routes_dir = dir.join("routes");
+        for route in self.get_routes()? {
+            if manifest.routes.get(&route.id) == Some(&route.updated_at)
+                && routes_dir.join(format!("{}.json", route.id)).exists()
+            {
+                continue;
+            }
+
+            let gpx = match self.export_route(&route.id, RouteFormat::Gpx) {
+                Ok(gpx) => gpx,
+                Err(e) => {
+                    report.failures.push(format!("route {}: {}", route.id, e));
+                    continue;
+                }
+            };
+            write_atomic(&routes_dir.join(format!("{}.gpx", route.id)), gpx).into_diagnostic()?;
+            write_json(&routes_dir.join(format!("{}.json", route.id)), &route)?;
+
+            manifest.routes.insert(route.id.clone(), route.updated_at);
+            manifest.save(dir)?;
+            report.routes_exported += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Writes every new or changed activity with its streams and track.
+    fn backup_activities(
+        &self,
+        dir: &Path,
+        manifest: &mut Manifest,
+        report: &mut BackupReport,
+    ) -> Result<()> {
+        // Oldest first, so an interrupted first run has backed up a contiguous history
+        let filter = ActivityFilter {
+            after: Some(0),
+            ..Default::default()
+        };
+
+        for activity in self.activities(filter)? {
+            let activity = activity?;
+            let fingerprint = fingerprint(&activity)?;
+            let activity_dir = activity_dir(dir, activity.id);
+
+            if manifest.activities.get(&activity.id) == Some(&fingerprint)
+                && activity_dir.join("activity.json").exists()
+            {
+                report.activities_unchanged += 1;
+                continue;
+            }
+
+            info!(
+                "Backing up {} {} {}",
+                activity.id,
+                activity.start_date.format("%Y-%m-%d"),
+                activity.name
+            );
+            if let Err(e) = self.backup_activity(&activity_dir, &activity) {
+                report
+                    .failures
+                    .push(format!("activity {}: {}", activity.id, e));
+                continue;
+            }
+
+            manifest.activities.insert(activity.id, fingerprint);
+            manifest.save(dir)?;
+            report.activities_fetched += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the detail, streams and track of one activity.
+    fn backup_activity(&self, activity_dir: &Path, activity: &Activity) -> Result<()> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        let query = [("include_all_efforts", "true".to_string())];
+        let detail: Value = api.get(&format!("activities/{}", activity.id), &query)?;
+        write_json(&activity_dir.join("activity.json"), &detail)?;
+
+        if activity.manual {
+            return Ok(());
+        }
+
+        let streams = streams::get_activity_streams(&api, activity.id, &StreamType::ALL, None)?;
+        write_json(&activity_dir.join("streams.json"), &streams)?;
+        if let Some(gpx) = streams::to_gpx(&activity.name, activity.start_date, &streams) {
+            write_atomic(&activity_dir.join("track.gpx"), gpx).into_diagnostic()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the directory of an activity in a backup.
+fn activity_dir(dir: &Path, id: i64) -> PathBuf {
+    dir.join("activities").join(id.to_string())
+}
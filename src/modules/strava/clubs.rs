@@ -0,0 +1,201 @@
+//! Strava clubs
+//!
+//! Lists the clubs of the authenticated athlete, their members and their
+//! activity feed, and totals the feed per member for a leaderboard.
+//!
+//! The club feed has no documented time filter and its activities carry no
+//! start time. The period of a leaderboard is sent as the `after` parameter
+//! the athlete activity list accepts; should Strava ignore it for clubs, the
+//! leaderboard totals the whole feed, which Strava keeps short.
+//! Docs: https://developers.strava.com/docs/reference/#api-Clubs
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::Api;
+use super::StravaError;
+
+/// Number of clubs, members or activities requested per page
+const PER_PAGE: usize = 200;
+
+/// Summary of a club
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Club {
+    /// Identifier of the club
+    pub id: i64,
+    /// Name of the club
+    pub name: String,
+    /// Sport of the club, e.g. `cycling` or `running`
+    pub sport_type: Option<String>,
+    /// City of the club
+    pub city: Option<String>,
+    /// State of the club
+    pub state: Option<String>,
+    /// Country of the club
+    pub country: Option<String>,
+    /// Whether the club is private
+    #[serde(default)]
+    pub private: bool,
+    /// Number of members
+    #[serde(default)]
+    pub member_count: i64,
+    /// Vanity part of the club URL
+    pub url: Option<String>,
+}
+
+/// A member of a club
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClubMember {
+    /// First name
+    pub firstname: String,
+    /// Initial of the last name, e.g. `S.`
+    pub lastname: String,
+    /// Membership status, e.g. `member` or `pending`
+    pub membership: Option<String>,
+    /// Whether the member is an administrator of the club
+    #[serde(default)]
+    pub admin: bool,
+    /// Whether the member owns the club
+    #[serde(default)]
+    pub owner: bool,
+}
+
+/// Athlete of a club activity, as much as Strava shares of them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClubAthlete {
+    /// First name
+    pub firstname: String,
+    /// Initial of the last name, e.g. `S.`
+    pub lastname: String,
+}
+
+impl ClubAthlete {
+    /// Returns the name shown for the athlete, e.g. `Peter S.`
+    pub fn display_name(&self) -> String {
+        format!("{} {}", self.firstname, self.lastname)
+    }
+}
+
+/// An activity of the club feed
+///
+/// Strava does not share the identifier or the start time of club activities.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClubActivity {
+    /// Athlete of the activity
+    pub athlete: ClubAthlete,
+    /// Name of the activity
+    pub name: String,
+    /// Sport type of the activity, e.g. `Run`
+    pub sport_type: String,
+    /// Distance (m)
+    #[serde(default)]
+    pub distance: f64,
+    /// Moving time (s)
+    #[serde(default)]
+    pub moving_time: i64,
+    /// Elapsed time (s)
+    #[serde(default)]
+    pub elapsed_time: i64,
+    /// Elevation gain (m)
+    #[serde(default)]
+    pub total_elevation_gain: f64,
+}
+
+/// Totals of one member over a period
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemberTotals {
+    /// Name shown for the member, e.g. `Peter S.`
+    pub athlete: String,
+    /// Number of activities
+    pub activities: usize,
+    /// Total distance (m)
+    pub distance: f64,
+    /// Total moving time (s)
+    pub moving_time: i64,
+    /// Total elapsed time (s)
+    pub elapsed_time: i64,
+    /// Total elevation gain (m)
+    pub elevation_gain: f64,
+}
+
+/// Totals club activities per member, ranked by distance then moving time.
+///
+/// Members are told apart by their shown name, as Strava shares nothing more.
+///
+/// # Arguments
+///
+/// * `activities` - Activities of the club feed
+pub fn leaderboard(activities: &[ClubActivity]) -> Vec<MemberTotals> {
+    let mut totals: HashMap<String, MemberTotals> = HashMap::new();
+    for activity in activities {
+        let name = activity.athlete.display_name();
+        let member = totals.entry(name.clone()).or_insert_with(|| MemberTotals {
+            athlete: name,
+            ..Default::default()
+        });
+        member.activities += 1;
+        member.distance += activity.distance;
+        member.moving_time += activity.moving_time;
+        member.elapsed_time += activity.elapsed_time;
+        member.elevation_gain += activity.total_elevation_gain;
+    }
+
+    let mut ranking: Vec<MemberTotals> = totals.into_values().collect();
+    ranking.sort_by(|a, b| {
+        b.distance
+            .total_cmp(&a.distance)
+            .then(b.moving_time.cmp(&a.moving_time))
+            .then_with(|| a.athlete.cmp(&b.athlete))
+    });
+    ranking
+}
+
+/// Retrieves the clubs of the authenticated athlete.
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn clubs(api: &Api) -> Result<Vec<Club>, StravaError> {
+    api.get_all_pages("athlete/clubs", &[], PER_PAGE)
+}
+
+/// Retrieves the members of a club.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the club
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn members(api: &Api, id: i64) -> Result<Vec<ClubMember>, StravaError> {
+    api.get_all_pages(&format!("clubs/{}/members", id), &[], PER_PAGE)
+}
+
+/// Retrieves the activity feed of a club, newest first.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the club
+/// * `after` - Unix timestamp sent as the undocumented `after` parameter,
+///   which cannot be checked against the activities
+/// * `limit` - Number of activities to stop at, or `None` for the whole feed
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn activities(
+    api: &Api,
+    id: i64,
+    after: Option<i64>,
+    limit: Option<usize>,
+) -> Result<Vec<ClubActivity>, StravaError> {
+    let query: Vec<(&str, String)> = after
+        .map(|after| ("after", after.to_string()))
+        .into_iter()
+        .collect();
+
+    api.get_pages(&format!("clubs/{}/activities", id), &query, PER_PAGE, limit)
+}
@@ -0,0 +1,176 @@
+//! Strava gear mileage
+//!
+//! Combines the shoes and bikes of the athlete profile with the activities of
+//! a period to report how far each item has gone, and flags items that reach
+//! their retirement distance.
+//! Docs: https://developers.strava.com/docs/reference/#api-Gears
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+use strava_client_rs::models::athlete::Gear;
+
+use super::Activity;
+
+/// Default share of the retirement distance at which an item is reported as close to it
+pub const DEFAULT_WARNING_RATIO: f64 = 0.9;
+
+/// Kind of gear
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GearKind {
+    /// Running or walking shoes
+    Shoe,
+    /// Bike
+    Bike,
+}
+
+impl fmt::Display for GearKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GearKind::Shoe => write!(f, "shoe"),
+            GearKind::Bike => write!(f, "bike"),
+        }
+    }
+}
+
+/// Where an item stands against its retirement distance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GearStatus {
+    /// No retirement distance, or far from it
+    Ok,
+    /// Past the warning share of the retirement distance
+    Warning,
+    /// At or past the retirement distance
+    Retire,
+}
+
+/// Retirement distances of gear
+///
+/// # Examples
+///
+/// ```rust
+/// use fit_connect_rs::modules::strava::gear::{GearStatus, GearThresholds};
+///
+/// // Retire shoes at 700 km, warn from 630 km
+/// let thresholds = GearThresholds {
+///     shoes: Some(700_000.0),
+///     ..Default::default()
+/// };
+/// assert_eq!(thresholds.status(650_000.0, Some(700_000.0)), GearStatus::Warning);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GearThresholds {
+    /// Retirement distance of every shoe (m)
+    pub shoes: Option<f64>,
+    /// Retirement distance of every bike (m)
+    pub bikes: Option<f64>,
+    /// Retirement distances of single items by gear id (m), taking precedence
+    /// over the distances by kind
+    pub overrides: HashMap<String, f64>,
+    /// Share of the retirement distance from which an item gets a warning
+    pub warning_ratio: f64,
+}
+
+impl Default for GearThresholds {
+    fn default() -> Self {
+        GearThresholds {
+            shoes: None,
+            bikes: None,
+            overrides: HashMap::new(),
+            warning_ratio: DEFAULT_WARNING_RATIO,
+        }
+    }
+}
+
+impl GearThresholds {
+    /// Returns the retirement distance of an item, if any.
+    pub fn threshold(&self, id: &str, kind: GearKind) -> Option<f64> {
+        self.overrides.get(id).copied().or(match kind {
+            GearKind::Shoe => self.shoes,
+            GearKind::Bike => self.bikes,
+        })
+    }
+
+    /// Returns the status of an item that has gone `distance` meters.
+    pub fn status(&self, distance: f64, threshold: Option<f64>) -> GearStatus {
+        match threshold {
+            Some(threshold) if distance >= threshold => GearStatus::Retire,
+            Some(threshold) if distance >= threshold * self.warning_ratio => GearStatus::Warning,
+            _ => GearStatus::Ok,
+        }
+    }
+}
+
+/// Mileage of a single item
+#[derive(Debug, Clone, Serialize)]
+pub struct GearUsage {
+    /// Identifier of the gear, e.g. `g1234567` or `b1234567`
+    pub id: String,
+    /// Name of the gear
+    pub name: String,
+    /// Kind of gear
+    pub kind: GearKind,
+    /// Whether this is the default gear of its kind
+    pub primary: bool,
+    /// Total distance recorded with the gear (m)
+    pub distance: f64,
+    /// Distance recorded with the gear during the period (m)
+    pub period_distance: f64,
+    /// Number of activities with the gear during the period
+    pub period_activities: usize,
+    /// Retirement distance (m)
+    pub threshold: Option<f64>,
+    /// Where the gear stands against its retirement distance
+    pub status: GearStatus,
+}
+
+/// Builds the mileage report of the athlete's gear.
+///
+/// # Arguments
+///
+/// * `shoes` - Shoes of the athlete profile
+/// * `bikes` - Bikes of the athlete profile
+/// * `activities` - Activities of the period
+/// * `thresholds` - Retirement distances
+pub(crate) fn usage(
+    shoes: &[Gear],
+    bikes: &[Gear],
+    activities: &[Activity],
+    thresholds: &GearThresholds,
+) -> Vec<GearUsage> {
+    let mut period: HashMap<&str, (f64, usize)> = HashMap::new();
+    for activity in activities {
+        if let Some(gear_id) = &activity.gear_id {
+            let entry = period.entry(gear_id.as_str()).or_default();
+            entry.0 += activity.distance;
+            entry.1 += 1;
+        }
+    }
+
+    let items = shoes
+        .iter()
+        .map(|gear| (gear, GearKind::Shoe))
+        .chain(bikes.iter().map(|gear| (gear, GearKind::Bike)));
+
+    items
+        .map(|(gear, kind)| {
+            let (period_distance, period_activities) =
+                period.get(gear.id.as_str()).copied().unwrap_or_default();
+            let threshold = thresholds.threshold(&gear.id, kind);
+            GearUsage {
+                id: gear.id.clone(),
+                name: gear.name.clone(),
+                kind,
+                primary: gear.primary,
+                distance: gear.distance,
+                period_distance,
+                period_activities,
+                threshold,
+                status: thresholds.status(gear.distance, threshold),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,1185 @@
+//! Strava API client module for athlete management and authentication.
+//!
+//! This module provides functionality to interact with the Strava API,
+//! including authentication, athlete data retrieval, weight updates and activities.
+//! All calls go through a [`StravaSession`].
+
+use chrono::{Local, NaiveDateTime};
+use log::debug;
+use miette::{Context, IntoDiagnostic, Result};
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::modules::config::{self, Config, ServiceConfig};
+use crate::modules::oauth::{self, OauthError};
+use crate::modules::provider::{ActivitySink, ProfileSink};
+use crate::modules::token::{self, Token};
+use crate::modules::workout::{Sport, Workout};
+
+use request::Api;
+use reqwest::blocking::Client;
+use strava_client_rs::models::{AthleteCollection, AthleteStats};
+
+pub mod activities;
+pub mod backup;
+pub mod clubs;
+pub mod gear;
+pub mod rate_limit;
+mod request;
+pub mod routes;
+pub mod segments;
+pub mod streams;
+pub mod uploads;
+
+pub use activities::{
+    Activities, Activity, ActivityFilter, ActivityUpdate, DetailedActivity, FieldChange,
+    NewActivity, PlannedEdit, Visibility,
+};
+pub use backup::BackupReport;
+pub use clubs::{Club, ClubActivity, ClubAthlete, ClubMember, MemberTotals};
+pub use gear::{GearKind, GearStatus, GearThresholds, GearUsage};
+pub use rate_limit::Quota;
+pub use routes::{Route, RouteFormat};
+pub use segments::{AthleteSegmentStats, DetailedSegment, Effort, EffortHistory, SegmentSummary};
+pub use streams::{Resolution, Stream, StreamData, StreamType, Streams};
+pub use uploads::{Upload, UploadOptions, UploadOutcome};
+
+/// Possible errors that can occur during Strava API operations.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum StravaError {
+    /// Authentication-related errors, such as invalid tokens or failed authentication attempts.
+    #[error("Authentication failed")]
+    #[diagnostic(code(strava::auth::failed))]
+    Authentication {
+        /// The underlying error that caused the authentication failure
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+        /// Helpful message for resolving the authentication issue
+        #[help]
+        help: Option<String>,
+    },
+
+    /// The athlete has not authorized the application yet.
+    #[error("Not authorized with Strava: no tokens in {}", path.display())]
+    #[diagnostic(
+        code(strava::auth::missing),
+        help("Authorize the application with `fit-connect-rs strava --register`")
+    )]
+    NotAuthorized {
+        /// Path of the token file
+        path: PathBuf,
+    },
+
+    /// Configuration-related errors, such as missing environment variables.
+    #[error("Configuration error: {message}")]
+    #[diagnostic(code(strava::config::invalid))]
+    Config {
+        /// Description of the configuration error
+        message: String,
+        /// Guidance on how to fix the configuration
+        #[help]
+        help: String,
+    },
+
+    /// API-related errors, such as failed requests or invalid responses.
+    #[error("API error: {message}")]
+    #[diagnostic(code(strava::api::error))]
+    Api {
+        /// Description of the API error
+        message: String,
+        /// Additional context about the API call
+        #[source_code]
+        src: Option<String>,
+    },
+
+    /// The Strava rate limit was reached and waiting for it to reset was not possible.
+    #[error("Rate limited: {message}")]
+    #[diagnostic(code(strava::api::rate_limited))]
+    RateLimited {
+        /// Description of the exhausted allowance
+        message: String,
+        /// Guidance on when to try again
+        #[help]
+        help: Option<String>,
+    },
+
+    /// Upload-related errors, such as unreadable or unsupported files.
+    #[error("Upload error: {message}")]
+    #[diagnostic(code(strava::upload::invalid))]
+    Upload {
+        /// Description of the upload error
+        message: String,
+        /// Guidance on how to fix the file
+        #[help]
+        help: Option<String>,
+    },
+}
+
+/// Time between two checks of an upload still being processed
+const UPLOAD_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A connection to the Strava API for a single athlete.
+///
+/// The session holds the application credentials, the file caching the
+/// athlete's tokens, the API and OAuth URLs, an HTTP client whose connections are reused by every
+/// request, and the athlete id once it is known. It is the entry point for
+/// every Strava API call; reuse one session for a whole run.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fit_connect_rs::StravaSession;
+///
+/// let session = StravaSession::from_env()?;
+/// let athlete = session.get_authenticated_athlete()?;
+/// println!("{}", athlete.get_full_name());
+/// # Ok::<(), miette::Report>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct StravaSession {
+    /// Client ID of the Strava application
+    client_id: String,
+    /// Client secret of the Strava application
+    client_secret: String,
+    /// Path of the file caching the tokens of the athlete
+    config_file: PathBuf,
+    /// Base URL of the API
+    api_url: String,
+    /// URL of the OAuth authorization page
+    auth_url: String,
+    /// URL of the OAuth token endpoint
+    token_url: String,
+    /// Comma separated OAuth scopes requested when authorizing
+    scope: String,
+    /// Local port the OAuth redirect is received on
+    redirect_port: u16,
+    /// HTTP client shared by every request of the session
+    client: Client,
+    /// Identifier of the authenticated athlete, fetched on first use
+    athlete_id: OnceLock<i64>,
+}
+
+impl StravaSession {
+    /// Creates a session from explicit application credentials.
+    ///
+    /// Tokens are cached in the file named by `STRAVA_CONFIG_FILE`, or
+    /// `strava.json` in the configuration directory if it is not set, and the
+    /// public Strava URLs are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - Client ID of the Strava application
+    /// * `client_secret` - Client secret of the Strava application
+    pub fn new(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        StravaSession {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            config_file: config::STRAVA.env_token_path(),
+            api_url: config::STRAVA.api_url.to_string(),
+            auth_url: config::STRAVA.auth_url.to_string(),
+            token_url: config::STRAVA.token_url.to_string(),
+            scope: config::STRAVA.scope.to_string(),
+            redirect_port: config::STRAVA.redirect_port,
+            client: Client::new(),
+            athlete_id: OnceLock::new(),
+        }
+    }
+
+    /// Creates a session from the `[strava]` section of a configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Strava configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Config` if the client ID or secret is not set.
+    pub fn from_config(config: &ServiceConfig) -> Result<Self, StravaError> {
+        let client_id = config
+            .client_id
+            .as_ref()
+            .ok_or_else(|| StravaError::Config {
+                message: "Missing client ID".to_string(),
+                help: format!(
+                    "Set strava.client_id in the configuration file or the {} environment variable",
+                    config::STRAVA.client_id_env
+                ),
+            })?;
+
+        let client_secret = config
+            .client_secret
+            .as_ref()
+            .ok_or_else(|| StravaError::Config {
+                message: "Missing client secret".to_string(),
+                help: format!(
+                    "Set strava.client_secret in the configuration file or the {} environment variable",
+                    config::STRAVA.client_secret_env
+                ),
+            })?;
+
+        Ok(StravaSession::new(client_id, client_secret).with_config(config))
+    }
+
+    /// Creates a session from the `STRAVA_CLIENT_ID` and `STRAVA_CLIENT_SECRET`
+    /// environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Config` if either environment variable is not set.
+    pub fn from_env() -> Result<Self, StravaError> {
+        StravaSession::from_config(&Config::from_env().strava)
+    }
+
+    /// Uses the token file and URLs set in a configuration, keeping the
+    /// credentials of the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Strava configuration
+    pub fn with_config(mut self, config: &ServiceConfig) -> Self {
+        if let Some(token_file) = &config.token_file {
+            self.config_file = token_file.clone();
+        }
+        if let Some(api_url) = &config.api_url {
+            self.api_url = api_url.clone();
+        }
+        if let Some(auth_url) = &config.auth_url {
+            self.auth_url = auth_url.clone();
+        }
+        if let Some(token_url) = &config.token_url {
+            self.token_url = token_url.clone();
+        }
+        if let Some(scope) = &config.scope {
+            self.scope = scope.clone();
+        }
+        if let Some(redirect_port) = config.redirect_port {
+            self.redirect_port = redirect_port;
+        }
+        self
+    }
+
+    /// Caches the athlete's tokens in another file, e.g. one per profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the token file
+    pub fn with_token_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = path.into();
+        self
+    }
+
+    /// Authenticates with the Strava API using OAuth2 flow.
+    ///
+    /// Asks the athlete to approve the application in the browser, receives
+    /// the redirect on `http://localhost:<redirect_port>`, checks its `state`
+    /// and stores the resulting tokens in the token file.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - Called with the URL of the authorization page, to show it
+    ///   to the athlete
+    ///
+    /// # Returns
+    /// - `Ok(Vec<String>)` - The requested scopes the athlete did not grant
+    /// - `Err(StravaError)` - If authentication fails
+    ///
+    /// # Errors
+    /// This function will return `StravaError::Authentication` if the OAuth2 flow fails
+    pub fn authorize(&self, prompt: impl FnOnce(&str)) -> Result<Vec<String>, StravaError> {
+        let authorization = oauth::Authorization {
+            service: "Strava",
+            auth_url: &self.auth_url,
+            client_id: &self.client_id,
+            scope: &self.scope,
+            port: self.redirect_port,
+            extra: &[("approval_prompt", "force")],
+        };
+        let grant = authorization.run(prompt).map_err(|e| {
+            // Only a redirect that never arrives points at the setup
+            let help = matches!(e, OauthError::Listen { .. } | OauthError::Timeout).then(|| {
+                format!(
+                    "Check that the Authorization Callback Domain of your Strava \
+                         application is localhost and that port {} is free",
+                    self.redirect_port
+                )
+            });
+            StravaError::Authentication {
+                source: e.into(),
+                help,
+            }
+        })?;
+
+        let token =
+            self.exchange_code(&grant.code)
+                .map_err(|source| StravaError::Authentication {
+                    source,
+                    help: Some("Check your Strava credentials and try again".to_string()),
+                })?;
+        token::store(&self.config_file, token).map_err(|e| StravaError::Authentication {
+            source: e.into(),
+            help: None,
+        })?;
+
+        Ok(grant.missing_scopes)
+    }
+
+    /// Retrieves the authenticated athlete's profile information from Strava.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the athlete's profile information if successful,
+    /// or a `StravaError` if the operation fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The API request fails
+    /// - The response cannot be parsed
+    pub fn get_authenticated_athlete(&self) -> Result<AthleteCollection> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        let athlete: AthleteCollection = api
+            .get("athlete", &[])
+            .wrap_err("Failed to get athlete information")?;
+        // Remember the id so later calls in the session need not fetch it again
+        let _ = self.athlete_id.set(athlete.id as i64);
+
+        Ok(athlete)
+    }
+
+    /// Retrieves statistics for the authenticated Strava athlete.
+    ///
+    /// This function fetches various statistics for the athlete, including total distance,
+    /// ride counts, run counts, and other activity metrics from the Strava API.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `AthleteStats` - The athlete's statistics
+    /// * `StravaError` - Error if the operation fails
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// * Authentication fails during access token retrieval
+    /// * The API request to get athlete stats fails
+    pub fn get_athlete_stats(&self) -> Result<AthleteStats> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+        let athlete_id = self.athlete_id()?;
+
+        api.get(&format!("athletes/{}/stats", athlete_id), &[])
+            .wrap_err("Failed to get athlete stats")
+    }
+
+    /// Updates the authenticated athlete's weight in Strava.
+    ///
+    /// # Arguments
+    ///
+    /// * `weight` - The athlete's weight in kilograms as a string
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the updated profile if successful,
+    /// or a `StravaError` if the operation fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The weight value is invalid
+    /// - The API request fails
+    pub fn update_athlete_weight(&self, weight: &str) -> Result<AthleteCollection> {
+        let api = self
+            .api()
+            .wrap_err("Failed to obtain access token for weight update")?;
+
+        api.put_form("athlete", &[("weight", weight.to_string())])
+            .wrap_err("Failed to update athlete weight")
+    }
+
+    /// Lists the activities of the authenticated athlete that meet a filter.
+    ///
+    /// No page is requested until the returned iterator is consumed.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Criteria the activities must meet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if authentication fails. Failed page requests are
+    /// returned by the iterator.
+    pub fn activities(&self, filter: ActivityFilter) -> Result<Activities> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(Activities::new(api, filter))
+    }
+
+    /// Retrieves the full representation of an activity, with its laps,
+    /// splits, best efforts and segment efforts.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the activity
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The activity does not exist or belongs to another athlete
+    pub fn get_activity(&self, id: i64) -> Result<DetailedActivity> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(activities::get(&api, id)?)
+    }
+
+    /// Retrieves the raw time series recorded during an activity.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the activity
+    /// * `types` - Stream types to retrieve, see [`StreamType::ALL`]
+    /// * `resolution` - Number of samples per stream, or every sample if `None`
+    ///
+    /// # Returns
+    ///
+    /// The streams keyed by type. Types the activity did not record are missing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The activity does not exist or belongs to another athlete
+    pub fn get_activity_streams(
+        &self,
+        id: i64,
+        types: &[StreamType],
+        resolution: Option<Resolution>,
+    ) -> Result<Streams> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(streams::get_activity_streams(&api, id, types, resolution)?)
+    }
+
+    /// Retrieves every activity of the authenticated athlete started between two times.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - Unix timestamp the activities start after
+    /// * `before` - Unix timestamp the activities start before
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The API request fails
+    pub fn get_activities_between(&self, after: i64, before: i64) -> Result<Vec<Activity>> {
+        let filter = ActivityFilter {
+            after: Some(after),
+            before: Some(before),
+            ..Default::default()
+        };
+
+        Ok(self.activities(filter)?.collect::<Result<_, _>>()?)
+    }
+
+    /// Creates a manual activity for the authenticated athlete.
+    ///
+    /// Requires the `activity:write` scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `activity` - The activity to create
+    ///
+    /// # Returns
+    ///
+    /// The created activity.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - Strava rejects the activity
+    pub fn create_manual_activity(&self, activity: &NewActivity) -> Result<Activity> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(activities::create(&api, activity)?)
+    }
+
+    /// Updates an activity of the authenticated athlete.
+    ///
+    /// Requires the `activity:write` scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the activity
+    /// * `update` - Changes to apply, fields left to `None` are not changed
+    ///
+    /// # Returns
+    ///
+    /// The updated activity.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The activity does not exist or belongs to another athlete
+    /// - Strava rejects the update
+    pub fn update_activity(&self, id: i64, update: &ActivityUpdate) -> Result<DetailedActivity> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(activities::update(&api, id, update)?)
+    }
+
+    /// Retrieves the current rate limit usage of the application.
+    ///
+    /// Makes a single request to read the usage Strava reports with it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The API request fails
+    /// - Strava did not report the usage
+    pub fn get_quota(&self) -> Result<Quota> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+        api.get::<serde_json::Value>("athlete", &[])?;
+
+        rate_limit::last_quota().ok_or_else(|| {
+            StravaError::Api {
+                message: "Strava did not report the rate limit usage".to_string(),
+                src: None,
+            }
+            .into()
+        })
+    }
+
+    /// Retrieves the segments starred by the authenticated athlete.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The API request fails
+    pub fn get_starred_segments(&self) -> Result<Vec<SegmentSummary>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(segments::starred(&api)?)
+    }
+
+    /// Retrieves the full representation of a segment, including the
+    /// authenticated athlete's personal record on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the segment
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The segment does not exist or is private
+    pub fn get_segment(&self, id: i64) -> Result<DetailedSegment> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(segments::get(&api, id)?)
+    }
+
+    /// Retrieves the authenticated athlete's efforts on a segment and works
+    /// out the personal record progression.
+    ///
+    /// Requires a Strava subscription.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the segment
+    /// * `start` - Only efforts started on or after this local time
+    /// * `end` - Only efforts started on or before this local time
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The segment does not exist or the athlete has no subscription
+    pub fn get_segment_history(
+        &self,
+        id: i64,
+        start: Option<NaiveDateTime>,
+        end: Option<NaiveDateTime>,
+    ) -> Result<EffortHistory> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+        let efforts = segments::efforts(&api, id, start, end)?;
+
+        Ok(EffortHistory::from_efforts(efforts))
+    }
+
+    /// Retrieves the clubs the authenticated athlete is a member of.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The API request fails
+    pub fn get_clubs(&self) -> Result<Vec<Club>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(clubs::clubs(&api)?)
+    }
+
+    /// Retrieves the members of a club.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the club
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The club does not exist or the athlete is not a member
+    pub fn get_club_members(&self, id: i64) -> Result<Vec<ClubMember>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(clubs::members(&api, id)?)
+    }
+
+    /// Retrieves the activity feed of a club, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the club
+    /// * `after` - Unix timestamp passed to Strava as `after`; the parameter is
+    ///   not documented for clubs and club activities have no start time, so
+    ///   the feed cannot be checked against it
+    /// * `limit` - Number of activities to stop at, or `None` for the whole feed
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The club does not exist or the athlete is not a member
+    pub fn get_club_activities(
+        &self,
+        id: i64,
+        after: Option<i64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<ClubActivity>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(clubs::activities(&api, id, after, limit)?)
+    }
+
+    /// Totals the club activities started after `after` per member.
+    ///
+    /// The period relies on Strava honouring `after` for the club feed, which
+    /// is not documented; if it is ignored the whole feed is totalled.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the club
+    /// * `after` - Unix timestamp the period starts at
+    /// * `sport_types` - Only activities of these sport types, compared
+    ///   case-insensitively; empty means every sport type
+    ///
+    /// # Returns
+    ///
+    /// The members ranked by distance.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The club does not exist or the athlete is not a member
+    pub fn get_club_leaderboard(
+        &self,
+        id: i64,
+        after: i64,
+        sport_types: &[String],
+    ) -> Result<Vec<MemberTotals>> {
+        let activities: Vec<ClubActivity> = self
+            .get_club_activities(id, Some(after), None)?
+            .into_iter()
+            .filter(|activity| {
+                sport_types.is_empty()
+                    || sport_types
+                        .iter()
+                        .any(|sport_type| sport_type.eq_ignore_ascii_case(&activity.sport_type))
+            })
+            .collect();
+
+        Ok(clubs::leaderboard(&activities))
+    }
+
+    /// Retrieves the routes saved by the authenticated athlete.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The API request fails
+    pub fn get_routes(&self) -> Result<Vec<Route>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+        let athlete_id = self.athlete_id()?;
+
+        Ok(routes::list(&api, athlete_id)?)
+    }
+
+    /// Retrieves a route.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the route
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The route does not exist or is private to another athlete
+    pub fn get_route(&self, id: &str) -> Result<Route> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(routes::get(&api, id)?)
+    }
+
+    /// Exports a route as a GPX or TCX file.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the route
+    /// * `format` - File format
+    ///
+    /// # Returns
+    ///
+    /// The contents of the file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The route does not exist or is private to another athlete
+    pub fn export_route(&self, id: &str, format: RouteFormat) -> Result<Vec<u8>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(routes::export(&api, id, format)?)
+    }
+
+    /// Exports a route into a directory, named after the route.
+    ///
+    /// # Arguments
+    ///
+    /// * `route` - The route to export
+    /// * `format` - File format
+    /// * `dir` - Directory to write the file to, created if missing
+    ///
+    /// # Returns
+    ///
+    /// The path of the written file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The route cannot be exported
+    /// - The file cannot be written
+    pub fn save_route(&self, route: &Route, format: RouteFormat, dir: &Path) -> Result<PathBuf> {
+        let contents = self.export_route(&route.id, format)?;
+        let path = dir.join(route.file_name(format));
+
+        fs::create_dir_all(dir)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+        fs::write(&path, contents)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(path)
+    }
+
+    /// Reports the mileage of the athlete's shoes and bikes against their
+    /// retirement distances.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - Unix timestamp the period starts at
+    /// * `before` - Unix timestamp the period ends at
+    /// * `thresholds` - Retirement distances
+    ///
+    /// # Returns
+    ///
+    /// One entry per item of the athlete profile, shoes first. Retired items
+    /// are not part of the profile.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The athlete profile or the activities cannot be retrieved
+    pub fn get_gear_usage(
+        &self,
+        after: i64,
+        before: i64,
+        thresholds: &GearThresholds,
+    ) -> Result<Vec<GearUsage>> {
+        let athlete = self
+            .get_authenticated_athlete()
+            .wrap_err("Failed to get athlete gear")?;
+        let activities = self.get_activities_between(after, before)?;
+
+        Ok(gear::usage(
+            athlete.shoes.as_deref().unwrap_or_default(),
+            athlete.bikes.as_deref().unwrap_or_default(),
+            &activities,
+            thresholds,
+        ))
+    }
+
+    /// Works out what an update would change on every activity that meets a filter.
+    ///
+    /// Nothing is modified; pass the result to [`StravaSession::apply_edits`]
+    /// to perform the changes. Activities the update would not change are left
+    /// out. The full activity is only retrieved when the update touches the
+    /// description or the home feed flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - Criteria the activities must meet
+    /// * `update` - Changes to apply
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - An activity cannot be retrieved
+    pub fn plan_edits(
+        &self,
+        filter: ActivityFilter,
+        update: &ActivityUpdate,
+    ) -> Result<Vec<PlannedEdit>> {
+        let mut edits = Vec::new();
+        for activity in self.activities(filter)? {
+            let activity = activity?;
+            let activity = if update.needs_detail() {
+                self.get_activity(activity.id)?
+            } else {
+                DetailedActivity::from(activity)
+            };
+
+            let changes = update.changes(&activity);
+            if !changes.is_empty() {
+                edits.push(PlannedEdit {
+                    id: activity.summary.id,
+                    name: activity.summary.name,
+                    start_date: activity.summary.start_date,
+                    changes,
+                });
+            }
+        }
+
+        Ok(edits)
+    }
+
+    /// Applies an update to every planned activity, stopping at the first failure.
+    ///
+    /// Requires the `activity:write` scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `edits` - Activities returned by [`StravaSession::plan_edits`]
+    /// * `update` - Changes to apply
+    ///
+    /// # Returns
+    ///
+    /// The updated activities.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Strava rejects an update; the activities before it
+    /// have been updated.
+    pub fn apply_edits(
+        &self,
+        edits: &[PlannedEdit],
+        update: &ActivityUpdate,
+    ) -> Result<Vec<DetailedActivity>> {
+        edits
+            .iter()
+            .map(|edit| {
+                self.update_activity(edit.id, update)
+                    .wrap_err_with(|| format!("Failed to update activity {}", edit.id))
+            })
+            .collect()
+    }
+
+    /// Uploads a FIT, TCX or GPX file, optionally gzip compressed.
+    ///
+    /// Strava processes the file in the background; pass the returned upload
+    /// to [`StravaSession::wait_for_upload`] to get the resulting activity.
+    /// Requires the `activity:write` scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file
+    /// * `options` - Metadata of the activity
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The file cannot be read or is not a supported type
+    /// - Authentication fails
+    /// - Strava rejects the upload
+    pub fn upload_file(&self, path: &Path, options: &UploadOptions) -> Result<Upload> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(uploads::create(&api, path, options)?)
+    }
+
+    /// Retrieves the current state of an upload.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Identifier of the upload
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - Authentication fails
+    /// - The upload does not exist
+    pub fn get_upload(&self, id: i64) -> Result<Upload> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(uploads::get(&api, id)?)
+    }
+
+    /// Polls an upload until Strava has processed it or `timeout` has elapsed.
+    ///
+    /// # Arguments
+    ///
+    /// * `upload` - The upload returned by [`StravaSession::upload_file`]
+    /// * `timeout` - Maximum time to wait
+    ///
+    /// # Returns
+    ///
+    /// The last state of the upload, which is still processing if the timeout elapsed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the upload cannot be retrieved.
+    pub fn wait_for_upload(&self, upload: Upload, timeout: Duration) -> Result<Upload> {
+        let deadline = Instant::now() + timeout;
+        let mut upload = upload;
+
+        while upload.outcome() == UploadOutcome::Processing && Instant::now() < deadline {
+            debug!("Upload {}: {}", upload.id, upload.status);
+            sleep(UPLOAD_POLL_INTERVAL);
+            upload = self.get_upload(upload.id)?;
+        }
+
+        Ok(upload)
+    }
+
+    /// Retrieves the identifier of the authenticated athlete, fetching the
+    /// athlete only if the session does not know it yet.
+    fn athlete_id(&self) -> Result<i64> {
+        if let Some(id) = self.athlete_id.get() {
+            return Ok(*id);
+        }
+
+        let athlete = self
+            .get_authenticated_athlete()
+            .wrap_err("Failed to get athlete ID")?;
+
+        Ok(athlete.id as i64)
+    }
+
+    /// Opens a connection to the Strava API with the session's HTTP client
+    /// and a valid access token.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no access token can be obtained.
+    fn api(&self) -> Result<Api> {
+        Ok(Api::new(
+            self.client.clone(),
+            &self.api_url,
+            self.obtain_access_token()?,
+        ))
+    }
+
+    /// Obtains an access token for Strava API operations.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the access token if successful,
+    /// or a `StravaError` if the operation fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The configuration file cannot be read
+    /// - The authentication process fails
+    fn obtain_access_token(&self) -> Result<String> {
+        self.get_access_token(&self.config_file)
+            .wrap_err("Failed to get access token")
+    }
+
+    /// Retrieves an access token from the token cache of a configuration file.
+    ///
+    /// The cached token is reused until it is about to expire, then refreshed
+    /// once and saved. Without a configuration file the athlete has to
+    /// [authorize](StravaSession::authorize) the application first.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_file` - Path to the configuration file
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the access token if successful,
+    /// or a `StravaError` if the operation fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The configuration file is invalid or cannot be written
+    /// - The application has not been authorized
+    fn get_access_token(&self, config_file: &Path) -> Result<String> {
+        let access_token = token::access_token(config_file, |refresh_token| {
+            self.refresh_access_token(refresh_token)
+        })
+        .map_err(|e| StravaError::Authentication {
+            source: e.into(),
+            help: Some("Check your credentials and network connection".to_string()),
+        })?;
+
+        access_token.ok_or_else(|| {
+            StravaError::NotAuthorized {
+                path: config_file.to_path_buf(),
+            }
+            .into()
+        })
+    }
+
+    /// Exchanges the authorization code of a redirect for the first tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - Authorization code received on the redirect
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or Strava refuses the code.
+    fn exchange_code(&self, code: &str) -> Result<Token, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self
+            .client
+            .post(&self.token_url)
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("grant_type", "authorization_code"),
+                ("code", code),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?)
+    }
+
+    /// Exchanges a refresh token for a new access token and refresh token.
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - The current refresh token
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or Strava refuses the refresh token.
+    fn refresh_access_token(&self, refresh_token: &str) -> reqwest::Result<Token> {
+        self.client
+            .post(&self.token_url)
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()
+    }
+}
+
+impl ProfileSink for StravaSession {
+    fn name(&self) -> &'static str {
+        "Strava"
+    }
+
+    fn update_weight(&self, weight_kg: f64) -> Result<()> {
+        self.update_athlete_weight(&weight_kg.to_string())
+            .map(|_| ())
+    }
+}
+
+/// Seconds between two start times for them to be considered the same activity
+const DUPLICATE_TOLERANCE: i64 = 120;
+
+/// Returns the Strava sport type of a sport
+fn sport_type(sport: Sport) -> &'static str {
+    match sport {
+        Sport::Walk => "Walk",
+        Sport::Run => "Run",
+        Sport::Hike => "Hike",
+        Sport::Ride => "Ride",
+        Sport::Swim => "Swim",
+        Sport::Rowing => "Rowing",
+        Sport::Ski => "AlpineSki",
+        Sport::Snowboard => "Snowboard",
+        Sport::Skate => "IceSkate",
+        Sport::Yoga => "Yoga",
+        Sport::Pilates => "Pilates",
+        Sport::WeightTraining => "WeightTraining",
+        Sport::Elliptical => "Elliptical",
+        Sport::Tennis => "Tennis",
+        Sport::Golf => "Golf",
+        Sport::Soccer => "Soccer",
+        Sport::Climbing => "RockClimbing",
+        Sport::Workout => "Workout",
+    }
+}
+
+impl ActivitySink for StravaSession {
+    fn name(&self) -> &'static str {
+        "Strava"
+    }
+
+    fn has_activity(&self, workout: &Workout) -> Result<bool> {
+        let start = workout.start.timestamp();
+        let existing =
+            self.get_activities_between(start - DUPLICATE_TOLERANCE, start + DUPLICATE_TOLERANCE)?;
+
+        Ok(!existing.is_empty())
+    }
+
+    fn create_activity(&self, workout: &Workout, origin: &str) -> Result<i64> {
+        let summary = workout.summary();
+        let mut description = format!("Imported from {} workout {}", origin, workout.id);
+        if !summary.is_empty() {
+            description = format!("{}: {}", description, summary);
+        }
+
+        let activity = NewActivity {
+            name: format!("{} {}", origin, workout.sport),
+            sport_type: sport_type(workout.sport).to_string(),
+            start_date_local: workout.start.with_timezone(&Local).naive_local(),
+            elapsed_time: workout.elapsed_time(),
+            description: Some(description),
+            distance: workout.distance,
+            trainer: workout.indoor,
+            commute: false,
+        };
+
+        self.create_manual_activity(&activity)
+            .map(|created| created.id)
+    }
+}
@@ -0,0 +1,390 @@
+//! Strava rate limit scheduling
+//!
+//! Strava allows a number of requests per 15 minutes, reset at the quarter
+//! hours, and per day, reset at midnight UTC, with a lower allowance for read
+//! requests. Every response reports the allowances and their usage in the
+//! `X-RateLimit-*` and `X-ReadRateLimit-*` headers.
+//!
+//! The last reported usage is kept for the whole process. Before a request the
+//! scheduler waits for the next 15-minute window if the current one is used
+//! up, and fails fast if the daily allowance is, since waiting until midnight
+//! is rarely wanted. The read allowance only counts for GET requests.
+//! Docs: https://developers.strava.com/docs/rate-limits/
+
+use chrono::{DateTime, Duration, DurationRound, NaiveTime, Utc};
+use log::warn;
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::thread::sleep;
+
+use super::StravaError;
+
+/// Length of the short rate limit window
+const SHORT_WINDOW_MINUTES: i64 = 15;
+
+/// Usage reported by the last response, shared by every session of the process
+static LAST_QUOTA: Mutex<Option<Quota>> = Mutex::new(None);
+
+/// Allowance and usage of one rate limit window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Window {
+    /// Number of requests allowed in the window
+    pub limit: u32,
+    /// Number of requests made in the window
+    pub usage: u32,
+}
+
+impl Window {
+    /// Returns the number of requests left in the window
+    pub fn remaining(&self) -> u32 {
+        self.limit.saturating_sub(self.usage)
+    }
+}
+
+/// Rate limit usage as reported by Strava
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Quota {
+    /// All requests in the current 15-minute window
+    pub short: Window,
+    /// All requests in the current day
+    pub daily: Window,
+    /// Read requests in the current 15-minute window, if reported
+    pub read_short: Option<Window>,
+    /// Read requests in the current day, if reported
+    pub read_daily: Option<Window>,
+    /// Time the usage was reported
+    pub observed_at: DateTime<Utc>,
+    /// Time the 15-minute window resets
+    pub short_reset_at: DateTime<Utc>,
+    /// Time the daily window resets
+    pub daily_reset_at: DateTime<Utc>,
+}
+
+impl Quota {
+    /// Reads the quota from the headers of a response.
+    ///
+    /// # Returns
+    ///
+    /// The quota, or `None` if the response has no rate limit headers.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Quota> {
+        Self::observed(headers, Utc::now())
+    }
+
+    /// Reads the quota from the headers of a response received at `observed_at`.
+    fn observed(headers: &HeaderMap, observed_at: DateTime<Utc>) -> Option<Quota> {
+        let (short, daily) = windows(headers, "x-ratelimit-limit", "x-ratelimit-usage")?;
+        let read = windows(headers, "x-readratelimit-limit", "x-readratelimit-usage");
+
+        Some(Quota {
+            short,
+            daily,
+            read_short: read.map(|(short, _)| short),
+            read_daily: read.map(|(_, daily)| daily),
+            observed_at,
+            short_reset_at: next_short_reset(observed_at),
+            daily_reset_at: next_daily_reset(observed_at),
+        })
+    }
+
+    /// Returns whether the 15-minute allowance is used up at `now`, counting
+    /// the read allowance only for a read request.
+    fn short_exhausted(&self, now: DateTime<Utc>, read: bool) -> bool {
+        now < self.short_reset_at
+            && (self.short.remaining() == 0
+                || read && self.read_short.is_some_and(|read| read.remaining() == 0))
+    }
+
+    /// Returns whether the daily allowance is used up at `now`, counting the
+    /// read allowance only for a read request.
+    fn daily_exhausted(&self, now: DateTime<Utc>, read: bool) -> bool {
+        now < self.daily_reset_at
+            && (self.daily.remaining() == 0
+                || read && self.read_daily.is_some_and(|read| read.remaining() == 0))
+    }
+}
+
+/// Parses a pair of `short,daily` limit and usage headers.
+fn windows(headers: &HeaderMap, limit: &str, usage: &str) -> Option<(Window, Window)> {
+    let pair = |name: &str| -> Option<(u32, u32)> {
+        let value = headers.get(name)?.to_str().ok()?;
+        let (short, daily) = value.split_once(',')?;
+        Some((short.trim().parse().ok()?, daily.trim().parse().ok()?))
+    };
+    let (short_limit, daily_limit) = pair(limit)?;
+    let (short_usage, daily_usage) = pair(usage)?;
+
+    Some((
+        Window {
+            limit: short_limit,
+            usage: short_usage,
+        },
+        Window {
+            limit: daily_limit,
+            usage: daily_usage,
+        },
+    ))
+}
+
+/// Returns the start of the next quarter hour after `time`.
+fn next_short_reset(time: DateTime<Utc>) -> DateTime<Utc> {
+    let window = Duration::minutes(SHORT_WINDOW_MINUTES);
+    time.duration_trunc(window).unwrap_or(time) + window
+}
+
+/// Returns the next midnight UTC after `time`.
+fn next_daily_reset(time: DateTime<Utc>) -> DateTime<Utc> {
+    (time.date_naive() + Duration::days(1))
+        .and_time(NaiveTime::MIN)
+        .and_utc()
+}
+
+/// Returns the usage reported by the last response of the process, if any.
+pub fn last_quota() -> Option<Quota> {
+    *LAST_QUOTA.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records the usage reported by a response.
+pub(crate) fn record(headers: &HeaderMap) {
+    if let Some(quota) = Quota::from_headers(headers) {
+        *LAST_QUOTA.lock().unwrap_or_else(|e| e.into_inner()) = Some(quota);
+    }
+}
+
+/// Waits until the next 15-minute window, after Strava refused a request.
+pub(crate) fn wait_for_next_window() {
+    wait_until(next_short_reset(Utc::now()));
+}
+
+/// Waits until a request fits in the quota.
+///
+/// # Arguments
+///
+/// * `method` - Method of the request; only GET requests count against the
+///   read allowance
+///
+/// # Errors
+///
+/// Returns `StravaError::RateLimited` if the daily allowance is used up.
+pub(crate) fn wait_for_budget(method: &Method) -> Result<(), StravaError> {
+    let Some(quota) = last_quota() else {
+        return Ok(());
+    };
+    let now = Utc::now();
+    let read = method == Method::GET;
+
+    if quota.daily_exhausted(now, read) {
+        return Err(StravaError::RateLimited {
+            message: format!(
+                "Daily request allowance used up until {}",
+                quota.daily_reset_at
+            ),
+            help: Some(
+                "Run the command again after the reset; backups resume where they stopped"
+                    .to_string(),
+            ),
+        });
+    }
+    if quota.short_exhausted(now, read) {
+        wait_until(quota.short_reset_at);
+    }
+
+    Ok(())
+}
+
+/// Sleeps until `time`, logging why.
+fn wait_until(time: DateTime<Utc>) {
+    let Ok(delay) = (time - Utc::now()).to_std() else {
+        return;
+    };
+
+    warn!(
+        "Strava 15-minute request allowance used up, waiting {} s until {}",
+        delay.as_secs(),
+        time.format("%H:%M UTC")
+    );
+    sleep(delay);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, hour, minute, second)
+            .unwrap()
+    }
+
+    fn quota(short_usage: u32, daily_usage: u32, read_usage: Option<(u32, u32)>) -> Quota {
+        let observed_at = at(10, 5, 0);
+        Quota {
+            short: Window {
+                limit: 200,
+                usage: short_usage,
+            },
+            daily: Window {
+                limit: 2000,
+                usage: daily_usage,
+            },
+            read_short: read_usage.map(|(usage, _)| Window { limit: 100, usage }),
+            read_daily: read_usage.map(|(_, usage)| Window { limit: 1000, usage }),
+            observed_at,
+            short_reset_at: next_short_reset(observed_at),
+            daily_reset_at: next_daily_reset(observed_at),
+        }
+    }
+
+    #[test]
+    fn parses_overall_limits() {
+        let headers = headers(&[
+            ("x-ratelimit-limit", "200,2000"),
+            ("x-ratelimit-usage", "12, 345"),
+        ]);
+
+        let quota = Quota::observed(&headers, at(10, 5, 0)).unwrap();
+        assert_eq!(
+            quota.short,
+            Window {
+                limit: 200,
+                usage: 12
+            }
+        );
+        assert_eq!(
+            quota.daily,
+            Window {
+                limit: 2000,
+                usage: 345
+            }
+        );
+        assert_eq!(quota.short.remaining(), 188);
+        assert_eq!(quota.read_short, None);
+        assert_eq!(quota.read_daily, None);
+        assert_eq!(quota.short_reset_at, at(10, 15, 0));
+        assert_eq!(
+            quota.daily_reset_at,
+            Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_read_limits() {
+        let headers = headers(&[
+            ("x-ratelimit-limit", "200,2000"),
+            ("x-ratelimit-usage", "12,345"),
+            ("x-readratelimit-limit", "100,1000"),
+            ("x-readratelimit-usage", "7,89"),
+        ]);
+
+        let quota = Quota::observed(&headers, at(10, 5, 0)).unwrap();
+        assert_eq!(
+            quota.read_short,
+            Some(Window {
+                limit: 100,
+                usage: 7
+            })
+        );
+        assert_eq!(
+            quota.read_daily,
+            Some(Window {
+                limit: 1000,
+                usage: 89
+            })
+        );
+    }
+
+    #[test]
+    fn ignores_missing_or_malformed_headers() {
+        assert_eq!(Quota::observed(&HeaderMap::new(), at(10, 5, 0)), None);
+        for (limit, usage) in [
+            ("200", "12,345"),
+            ("200,2000", "12;345"),
+            ("200,x", "12,345"),
+        ] {
+            let headers = headers(&[("x-ratelimit-limit", limit), ("x-ratelimit-usage", usage)]);
+            assert_eq!(Quota::observed(&headers, at(10, 5, 0)), None);
+        }
+        let headers = headers(&[("x-ratelimit-limit", "200,2000")]);
+        assert_eq!(Quota::observed(&headers, at(10, 5, 0)), None);
+    }
+
+    #[test]
+    fn malformed_read_headers_keep_overall_limits() {
+        let headers = headers(&[
+            ("x-ratelimit-limit", "200,2000"),
+            ("x-ratelimit-usage", "12,345"),
+            ("x-readratelimit-limit", "100"),
+            ("x-readratelimit-usage", "7,89"),
+        ]);
+
+        let quota = Quota::observed(&headers, at(10, 5, 0)).unwrap();
+        assert_eq!(quota.short.usage, 12);
+        assert_eq!(quota.read_short, None);
+        assert_eq!(quota.read_daily, None);
+    }
+
+    #[test]
+    fn short_window_resets_at_the_next_quarter_hour() {
+        assert_eq!(next_short_reset(at(10, 0, 0)), at(10, 15, 0));
+        assert_eq!(next_short_reset(at(10, 14, 59)), at(10, 15, 0));
+        assert_eq!(next_short_reset(at(10, 45, 1)), at(11, 0, 0));
+        assert_eq!(
+            next_short_reset(at(23, 50, 0)),
+            Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn daily_window_resets_at_midnight_utc() {
+        let midnight = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        assert_eq!(next_daily_reset(at(0, 0, 0)), midnight);
+        assert_eq!(next_daily_reset(at(23, 59, 59)), midnight);
+    }
+
+    #[test]
+    fn exhausted_until_the_window_resets() {
+        let quota = quota(200, 2000, None);
+
+        assert!(quota.short_exhausted(at(10, 14, 59), false));
+        assert!(!quota.short_exhausted(at(10, 15, 0), false));
+        assert!(quota.daily_exhausted(at(23, 59, 59), false));
+        assert!(!quota.daily_exhausted(quota.daily_reset_at, false));
+    }
+
+    #[test]
+    fn not_exhausted_with_requests_left() {
+        let quota = quota(199, 1999, Some((99, 999)));
+
+        assert!(!quota.short_exhausted(at(10, 6, 0), true));
+        assert!(!quota.daily_exhausted(at(10, 6, 0), true));
+    }
+
+    #[test]
+    fn read_allowance_only_blocks_reads() {
+        let quota = quota(150, 1500, Some((100, 1000)));
+
+        assert!(quota.short_exhausted(at(10, 6, 0), true));
+        assert!(quota.daily_exhausted(at(10, 6, 0), true));
+        assert!(!quota.short_exhausted(at(10, 6, 0), false));
+        assert!(!quota.daily_exhausted(at(10, 6, 0), false));
+    }
+
+    #[test]
+    fn overall_allowance_blocks_writes() {
+        let quota = quota(200, 1500, Some((50, 500)));
+
+        assert!(quota.short_exhausted(at(10, 6, 0), false));
+        assert!(quota.short_exhausted(at(10, 6, 0), true));
+        assert!(!quota.daily_exhausted(at(10, 6, 0), false));
+    }
+}
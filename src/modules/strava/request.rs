@@ -0,0 +1,336 @@
+//! Request helpers for the Strava API
+//!
+//! Failed requests are turned into `StravaError::Api` with the response body
+//! attached, so the Strava error message is shown to the user. Every request
+//! goes through the [rate limit scheduler](super::rate_limit).
+
+use log::{trace, warn};
+use reqwest::blocking::{multipart::Form, Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{rate_limit, StravaError};
+
+/// Number of times a request refused for exceeding the rate limit is retried
+const RATE_LIMIT_RETRIES: usize = 2;
+
+/// Authenticated connection to the Strava API
+///
+/// Holds the HTTP client of the session, so that requests reuse its
+/// connections, the base URL of the API and the access token sent with every
+/// request.
+#[derive(Debug, Clone)]
+pub(crate) struct Api {
+    /// HTTP client of the session
+    client: Client,
+    /// Base URL of the v3 API, without a trailing slash
+    base_url: String,
+    /// Access token of the athlete
+    access_token: String,
+}
+
+impl Api {
+    /// Creates a connection from the HTTP client of a session, the base URL
+    /// of the API and an access token.
+    pub(crate) fn new(client: Client, base_url: &str, access_token: String) -> Self {
+        Api {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            access_token,
+        }
+    }
+
+    /// Returns the URL of an endpoint.
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    /// Sends a GET request to a Strava API endpoint and parses the JSON response.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `athlete/activities`
+    /// * `query` - Query parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if the request fails, Strava returns an error
+    /// status or the response cannot be parsed.
+    pub(crate) fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T, StravaError> {
+        trace!("Strava GET {} query parameters: {:?}", path, query);
+        let request = self
+            .client
+            .get(self.url(path))
+            .bearer_auth(&self.access_token)
+            .query(query);
+
+        send(Method::GET, request, path)
+    }
+
+    /// Sends a GET request to a Strava API endpoint and returns the raw response body,
+    /// for endpoints that return files rather than JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `routes/1/export_gpx`
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if the request fails or Strava returns an error status.
+    pub(crate) fn get_bytes(&self, path: &str) -> Result<Vec<u8>, StravaError> {
+        trace!("Strava GET {}", path);
+        let request = self
+            .client
+            .get(self.url(path))
+            .bearer_auth(&self.access_token);
+
+        let bytes =
+            send_raw(Method::GET, request, path)?
+                .bytes()
+                .map_err(|e| StravaError::Api {
+                    message: format!("Failed to read the response of {}", path),
+                    src: Some(e.to_string()),
+                })?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Sends GET requests for every page of a list endpoint and collects the items.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `segments/starred`
+    /// * `query` - Query parameters, without the paging parameters
+    /// * `per_page` - Number of items requested per page
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+    pub(crate) fn get_all_pages<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+        per_page: usize,
+    ) -> Result<Vec<T>, StravaError> {
+        self.get_pages(path, query, per_page, None)
+    }
+
+    /// Sends GET requests for the pages of a list endpoint until `limit` items
+    /// are collected or the last page is reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `segments/starred`
+    /// * `query` - Query parameters, without the paging parameters
+    /// * `per_page` - Largest number of items requested per page
+    /// * `limit` - Number of items to stop at, or `None` for every item
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+    pub(crate) fn get_pages<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+        per_page: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<T>, StravaError> {
+        let limit = limit.unwrap_or(usize::MAX);
+        // Pages of a fixed size, so the page numbers stay aligned
+        let per_page = per_page.min(limit).max(1);
+        let mut items = Vec::new();
+
+        for page in 1.. {
+            if items.len() >= limit {
+                break;
+            }
+            let mut page_query = query.to_vec();
+            page_query.push(("page", page.to_string()));
+            page_query.push(("per_page", per_page.to_string()));
+
+            let batch: Vec<T> = self.get(path, &page_query)?;
+            let last_page = batch.len() < per_page;
+            items.extend(batch);
+
+            if last_page {
+                break;
+            }
+        }
+
+        items.truncate(limit);
+        Ok(items)
+    }
+
+    /// Sends a POST request with form parameters to a Strava API endpoint and
+    /// parses the JSON response.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `activities`
+    /// * `form` - Form parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if the request fails, Strava returns an error
+    /// status or the response cannot be parsed.
+    pub(crate) fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        form: &[(&str, String)],
+    ) -> Result<T, StravaError> {
+        trace!("Strava POST {} form parameters: {:?}", path, form);
+        let request = self
+            .client
+            .post(self.url(path))
+            .bearer_auth(&self.access_token)
+            .form(form);
+
+        send(Method::POST, request, path)
+    }
+
+    /// Sends a PUT request with a JSON body to a Strava API endpoint and parses
+    /// the JSON response.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `activities/1`
+    /// * `body` - Body to serialize as JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if the request fails, Strava returns an error
+    /// status or the response cannot be parsed.
+    pub(crate) fn put<T: DeserializeOwned, B: Serialize + ?Sized>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, StravaError> {
+        let request = self
+            .client
+            .put(self.url(path))
+            .bearer_auth(&self.access_token)
+            .json(body);
+
+        send(Method::PUT, request, path)
+    }
+
+    /// Sends a PUT request with form parameters to a Strava API endpoint and
+    /// parses the JSON response.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `athlete`
+    /// * `form` - Form parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if the request fails, Strava returns an error
+    /// status or the response cannot be parsed.
+    pub(crate) fn put_form<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        form: &[(&str, String)],
+    ) -> Result<T, StravaError> {
+        trace!("Strava PUT {} form parameters: {:?}", path, form);
+        let request = self
+            .client
+            .put(self.url(path))
+            .bearer_auth(&self.access_token)
+            .form(form);
+
+        send(Method::PUT, request, path)
+    }
+
+    /// Sends a POST request with a multipart form to a Strava API endpoint and
+    /// parses the JSON response.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint relative to the v3 API, e.g. `uploads`
+    /// * `form` - Multipart form, including any file parts
+    ///
+    /// # Errors
+    ///
+    /// Returns `StravaError::Api` if the request fails, Strava returns an error
+    /// status or the response cannot be parsed.
+    pub(crate) fn post_multipart<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        form: Form,
+    ) -> Result<T, StravaError> {
+        trace!("Strava POST {} multipart form", path);
+        let request = self
+            .client
+            .post(self.url(path))
+            .bearer_auth(&self.access_token)
+            .multipart(form);
+
+        send(Method::POST, request, path)
+    }
+}
+
+/// Sends a request and parses the JSON response.
+fn send<T: DeserializeOwned>(
+    method: Method,
+    request: RequestBuilder,
+    path: &str,
+) -> Result<T, StravaError> {
+    send_raw(method, request, path)?
+        .json()
+        .map_err(|e| StravaError::Api {
+            message: format!("Failed to parse the response of {}", path),
+            src: Some(e.to_string()),
+        })
+}
+
+/// Sends a request within the rate limit and returns the response if Strava
+/// reports success.
+///
+/// A request refused with 429 is retried in the next 15-minute window, unless
+/// its body cannot be replayed.
+fn send_raw(method: Method, request: RequestBuilder, path: &str) -> Result<Response, StravaError> {
+    let mut request = request;
+    let mut retries = 0;
+
+    let response = loop {
+        rate_limit::wait_for_budget(&method)?;
+        let replay = request.try_clone();
+        let response = request.send().map_err(|e| StravaError::Api {
+            message: format!("Request to {} failed", path),
+            src: Some(e.to_string()),
+        })?;
+        rate_limit::record(response.headers());
+
+        if response.status() != StatusCode::TOO_MANY_REQUESTS {
+            break response;
+        }
+        match replay {
+            Some(replay) if retries < RATE_LIMIT_RETRIES => {
+                warn!("Strava {} refused for exceeding the rate limit", path);
+                rate_limit::wait_for_next_window();
+                request = replay;
+                retries += 1;
+            }
+            _ => {
+                return Err(StravaError::RateLimited {
+                    message: format!("{} refused for exceeding the rate limit", path),
+                    help: Some("Check the remaining allowance with `strava quota`".to_string()),
+                })
+            }
+        }
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        warn!("Strava {} returned {}", path, status);
+        return Err(StravaError::Api {
+            message: format!("{} returned {}", path, status),
+            src: response.text().ok(),
+        });
+    }
+
+    Ok(response)
+}
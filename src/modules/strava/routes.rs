@@ -0,0 +1,136 @@
+//! Strava routes
+//!
+//! Lists the routes saved by the authenticated athlete and exports them as
+//! GPX or TCX files for devices that do not sync with Strava.
+//! Docs: https://developers.strava.com/docs/reference/#api-Routes
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::request::Api;
+use super::StravaError;
+
+/// Number of routes requested per page
+const PER_PAGE: usize = 200;
+
+/// File format a route can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum RouteFormat {
+    /// GPS Exchange Format, a track without timing
+    Gpx,
+    /// Training Center XML, a course with estimated timing
+    Tcx,
+}
+
+impl RouteFormat {
+    /// Returns the file extension of the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            RouteFormat::Gpx => "gpx",
+            RouteFormat::Tcx => "tcx",
+        }
+    }
+}
+
+/// A route saved by the athlete
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Route {
+    /// Identifier of the route, kept as text as route identifiers exceed the
+    /// precision of JSON numbers in most tools
+    #[serde(rename(deserialize = "id_str"))]
+    pub id: String,
+    /// Name of the route
+    pub name: String,
+    /// Description of the route
+    pub description: Option<String>,
+    /// Length of the route (m)
+    #[serde(default)]
+    pub distance: f64,
+    /// Elevation gain (m)
+    #[serde(default)]
+    pub elevation_gain: f64,
+    /// Kind of route, 1 for a ride and 2 for a run
+    #[serde(rename(deserialize = "type"))]
+    pub route_type: Option<i64>,
+    /// Whether the route is private
+    #[serde(default)]
+    pub private: bool,
+    /// Whether the athlete starred the route
+    #[serde(default)]
+    pub starred: bool,
+    /// Estimated moving time (s)
+    pub estimated_moving_time: Option<i64>,
+    /// Creation time of the route
+    pub created_at: Option<DateTime<Utc>>,
+    /// Last modification time of the route
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Route {
+    /// Returns a file name for the route in a format, e.g. `lake-loop-3245.gpx`
+    pub fn file_name(&self, format: RouteFormat) -> String {
+        let mut slug = String::new();
+        for c in self.name.chars().flat_map(char::to_lowercase) {
+            if c.is_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        let slug = slug.trim_end_matches('-');
+
+        if slug.is_empty() {
+            format!("{}.{}", self.id, format.extension())
+        } else {
+            format!("{}-{}.{}", slug, self.id, format.extension())
+        }
+    }
+}
+
+/// Retrieves the routes of an athlete.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `athlete_id` - Identifier of the athlete
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn list(api: &Api, athlete_id: i64) -> Result<Vec<Route>, StravaError> {
+    api.get_all_pages(&format!("athletes/{}/routes", athlete_id), &[], PER_PAGE)
+}
+
+/// Retrieves a route.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the route
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if the route does not exist or cannot be parsed.
+pub(crate) fn get(api: &Api, id: &str) -> Result<Route, StravaError> {
+    api.get(&format!("routes/{}", id), &[])
+}
+
+/// Exports a route as a file.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the route
+/// * `format` - File format
+///
+/// # Returns
+///
+/// The contents of the file.
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if the route does not exist or the request fails.
+pub(crate) fn export(api: &Api, id: &str, format: RouteFormat) -> Result<Vec<u8>, StravaError> {
+    api.get_bytes(&format!("routes/{}/export_{}", id, format.extension()))
+}
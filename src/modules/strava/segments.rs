@@ -0,0 +1,250 @@
+//! Strava segments and segment efforts
+//!
+//! Segments are portions of road or trail where athletes compare times. Every
+//! pass over a segment is recorded as an [`Effort`]; the effort history of a
+//! segment requires a Strava subscription.
+//! Docs: https://developers.strava.com/docs/reference/#api-Segments
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::request::Api;
+use super::StravaError;
+
+/// Number of segments or efforts requested per page
+const PER_PAGE: usize = 200;
+
+/// Summary of a segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentSummary {
+    /// Identifier of the segment
+    pub id: i64,
+    /// Name of the segment
+    pub name: String,
+    /// Activity type of the segment, e.g. `Ride` or `Run`
+    pub activity_type: Option<String>,
+    /// Length of the segment (m)
+    #[serde(default)]
+    pub distance: f64,
+    /// Average grade (%)
+    pub average_grade: Option<f64>,
+    /// Maximum grade (%)
+    pub maximum_grade: Option<f64>,
+    /// Highest elevation (m)
+    pub elevation_high: Option<f64>,
+    /// Lowest elevation (m)
+    pub elevation_low: Option<f64>,
+    /// Climb category, 0 for uncategorized up to 5 for hors catégorie
+    pub climb_category: Option<i64>,
+    /// City of the segment
+    pub city: Option<String>,
+    /// State of the segment
+    pub state: Option<String>,
+    /// Country of the segment
+    pub country: Option<String>,
+    /// Whether the segment is private
+    #[serde(default)]
+    pub private: bool,
+    /// Whether the authenticated athlete starred the segment
+    #[serde(default)]
+    pub starred: bool,
+}
+
+/// A single pass over a segment or a best effort over a standard distance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Effort {
+    /// Identifier of the effort
+    pub id: i64,
+    /// Name of the segment or of the distance, e.g. `5k`
+    pub name: String,
+    /// Identifier of the activity the effort belongs to
+    #[serde(
+        default,
+        rename(deserialize = "activity"),
+        deserialize_with = "deserialize_activity_id"
+    )]
+    pub activity_id: Option<i64>,
+    /// Elapsed time (s)
+    pub elapsed_time: i64,
+    /// Moving time (s)
+    #[serde(default)]
+    pub moving_time: i64,
+    /// Start time of the effort
+    pub start_date: DateTime<Utc>,
+    /// Start time of the effort in the athlete's timezone
+    pub start_date_local: Option<String>,
+    /// Distance of the effort (m)
+    #[serde(default)]
+    pub distance: f64,
+    /// Average heart rate (bpm)
+    pub average_heartrate: Option<f64>,
+    /// Average power (W)
+    pub average_watts: Option<f64>,
+    /// Rank of the effort among the athlete's efforts, 1 for a personal record
+    pub pr_rank: Option<i64>,
+    /// Rank of the effort on the segment leaderboard, if in the top 10
+    pub kom_rank: Option<i64>,
+    /// Segment the effort was on, absent for best efforts
+    pub segment: Option<SegmentSummary>,
+}
+
+/// Reads the activity identifier from the `{"id": ...}` object Strava nests it in.
+fn deserialize_activity_id<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct ActivityRef {
+        id: i64,
+    }
+
+    Ok(Option::<ActivityRef>::deserialize(deserializer)?.map(|activity| activity.id))
+}
+
+/// Statistics of the authenticated athlete on a segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AthleteSegmentStats {
+    /// Elapsed time of the personal record (s)
+    pub pr_elapsed_time: Option<i64>,
+    /// Day of the personal record
+    pub pr_date: Option<String>,
+    /// Number of efforts of the athlete on the segment
+    #[serde(default)]
+    pub effort_count: i64,
+}
+
+/// Full representation of a segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetailedSegment {
+    /// Fields shared with the segment lists
+    #[serde(flatten)]
+    pub summary: SegmentSummary,
+    /// Elevation gain (m)
+    pub total_elevation_gain: Option<f64>,
+    /// Number of efforts by every athlete
+    #[serde(default)]
+    pub effort_count: i64,
+    /// Number of athletes who rode or ran the segment
+    #[serde(default)]
+    pub athlete_count: i64,
+    /// Number of athletes who starred the segment
+    #[serde(default)]
+    pub star_count: i64,
+    /// Statistics of the authenticated athlete on the segment
+    pub athlete_segment_stats: Option<AthleteSegmentStats>,
+}
+
+/// Efforts of the athlete on one segment, with the personal record progression
+#[derive(Debug, Clone, Serialize)]
+pub struct EffortHistory {
+    /// Every effort, in chronological order
+    pub efforts: Vec<Effort>,
+    /// Efforts that set a new personal record when they happened, in chronological order
+    pub pr_progression: Vec<Effort>,
+    /// Fastest effort
+    pub best: Option<Effort>,
+    /// Most recent effort
+    pub latest: Option<Effort>,
+    /// Seconds between the most recent effort and the personal record, 0 if
+    /// the most recent effort is the record
+    pub gap_to_pr: Option<i64>,
+}
+
+impl EffortHistory {
+    /// Builds the history of a segment from its efforts, in any order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fit_connect_rs::modules::strava::segments::EffortHistory;
+    ///
+    /// let history = EffortHistory::from_efforts(Vec::new());
+    /// assert!(history.best.is_none());
+    /// assert_eq!(history.gap_to_pr, None);
+    /// ```
+    pub fn from_efforts(mut efforts: Vec<Effort>) -> Self {
+        efforts.sort_by_key(|effort| effort.start_date);
+
+        let mut pr_progression: Vec<Effort> = Vec::new();
+        for effort in &efforts {
+            let is_pr = pr_progression
+                .last()
+                .is_none_or(|pr| effort.elapsed_time < pr.elapsed_time);
+            if is_pr {
+                pr_progression.push(effort.clone());
+            }
+        }
+
+        let best = pr_progression.last().cloned();
+        let latest = efforts.last().cloned();
+        let gap_to_pr = best
+            .as_ref()
+            .zip(latest.as_ref())
+            .map(|(best, latest)| latest.elapsed_time - best.elapsed_time);
+
+        EffortHistory {
+            efforts,
+            pr_progression,
+            best,
+            latest,
+            gap_to_pr,
+        }
+    }
+}
+
+/// Retrieves the segments starred by the authenticated athlete.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn starred(api: &Api) -> Result<Vec<SegmentSummary>, StravaError> {
+    api.get_all_pages("segments/starred", &[], PER_PAGE)
+}
+
+/// Retrieves the full representation of a segment.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the segment
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if the segment does not exist or cannot be parsed.
+pub(crate) fn get(api: &Api, id: i64) -> Result<DetailedSegment, StravaError> {
+    api.get(&format!("segments/{}", id), &[])
+}
+
+/// Retrieves the efforts of the authenticated athlete on a segment.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the segment
+/// * `start` - Only efforts started on or after this local time
+/// * `end` - Only efforts started on or before this local time
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn efforts(
+    api: &Api,
+    id: i64,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+) -> Result<Vec<Effort>, StravaError> {
+    let format = |time: NaiveDateTime| time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let mut query = vec![("segment_id", id.to_string())];
+    if let Some(start) = start {
+        query.push(("start_date_local", format(start)));
+    }
+    if let Some(end) = end {
+        query.push(("end_date_local", format(end)));
+    }
+
+    api.get_all_pages("segment_efforts", &query, PER_PAGE)
+}
@@ -0,0 +1,223 @@
+//! Strava activity streams
+//!
+//! Streams are the raw time series recorded during an activity, one value per
+//! sample for each requested type.
+//! Docs: https://developers.strava.com/docs/reference/#api-Streams
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::request::Api;
+use super::StravaError;
+
+/// Kind of time series recorded during an activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum StreamType {
+    /// Seconds since the start of the activity
+    Time,
+    /// Distance covered since the start (m)
+    Distance,
+    /// Latitude and longitude pairs
+    Latlng,
+    /// Altitude (m)
+    Altitude,
+    /// Smoothed speed (m/s)
+    VelocitySmooth,
+    /// Heart rate (bpm)
+    Heartrate,
+    /// Cadence (rpm)
+    Cadence,
+    /// Power (W)
+    Watts,
+    /// Temperature (°C)
+    Temp,
+    /// Whether the athlete was moving
+    Moving,
+    /// Smoothed grade (%)
+    GradeSmooth,
+}
+
+impl StreamType {
+    /// Every stream type
+    pub const ALL: [StreamType; 11] = [
+        StreamType::Time,
+        StreamType::Distance,
+        StreamType::Latlng,
+        StreamType::Altitude,
+        StreamType::VelocitySmooth,
+        StreamType::Heartrate,
+        StreamType::Cadence,
+        StreamType::Watts,
+        StreamType::Temp,
+        StreamType::Moving,
+        StreamType::GradeSmooth,
+    ];
+
+    /// Returns the key of the stream type in the API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StreamType::Time => "time",
+            StreamType::Distance => "distance",
+            StreamType::Latlng => "latlng",
+            StreamType::Altitude => "altitude",
+            StreamType::VelocitySmooth => "velocity_smooth",
+            StreamType::Heartrate => "heartrate",
+            StreamType::Cadence => "cadence",
+            StreamType::Watts => "watts",
+            StreamType::Temp => "temp",
+            StreamType::Moving => "moving",
+            StreamType::GradeSmooth => "grade_smooth",
+        }
+    }
+}
+
+/// Number of samples returned per stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Resolution {
+    /// About 100 samples
+    Low,
+    /// About 1000 samples
+    Medium,
+    /// About 10000 samples
+    High,
+}
+
+impl Resolution {
+    /// Returns the value of the resolution in the API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Resolution::Low => "low",
+            Resolution::Medium => "medium",
+            Resolution::High => "high",
+        }
+    }
+}
+
+/// Samples of a stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StreamData {
+    /// Numeric samples, e.g. time or heart rate
+    Numbers(Vec<f64>),
+    /// Latitude and longitude pairs
+    LatLng(Vec<[f64; 2]>),
+    /// Boolean samples, e.g. moving
+    Flags(Vec<bool>),
+}
+
+/// A time series recorded during an activity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stream {
+    /// Number of samples recorded before any downsampling
+    pub original_size: i64,
+    /// Resolution of the returned samples
+    pub resolution: String,
+    /// Base series the samples are aligned to, `time` or `distance`
+    pub series_type: String,
+    /// The samples
+    pub data: StreamData,
+}
+
+/// Streams of an activity keyed by type
+pub type Streams = BTreeMap<StreamType, Stream>;
+
+/// Builds a GPX track from the position streams of an activity.
+///
+/// Needs the `latlng` and `time` streams; `altitude` is included when present.
+///
+/// # Arguments
+///
+/// * `name` - Name of the track
+/// * `start` - Start time of the activity
+/// * `streams` - Streams of the activity
+///
+/// # Returns
+///
+/// The GPX document, or `None` if the activity has no position.
+pub fn to_gpx(name: &str, start: DateTime<Utc>, streams: &Streams) -> Option<String> {
+    let Some(Stream {
+        data: StreamData::LatLng(positions),
+        ..
+    }) = streams.get(&StreamType::Latlng)
+    else {
+        return None;
+    };
+    let Some(Stream {
+        data: StreamData::Numbers(times),
+        ..
+    }) = streams.get(&StreamType::Time)
+    else {
+        return None;
+    };
+    let altitudes = match streams.get(&StreamType::Altitude) {
+        Some(Stream {
+            data: StreamData::Numbers(altitudes),
+            ..
+        }) => altitudes.as_slice(),
+        _ => &[],
+    };
+
+    let name = name
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    let mut gpx = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<gpx version="1.1" creator="fit-connect-rs" xmlns="http://www.topografix.com/GPX/1/1">"#,
+        "\n"
+    ));
+    let _ = writeln!(gpx, "  <trk>\n    <name>{}</name>\n    <trkseg>", name);
+    for (i, ([lat, lon], seconds)) in positions.iter().zip(times).enumerate() {
+        let time = start + Duration::milliseconds((seconds * 1000.0) as i64);
+        let _ = write!(gpx, r#"      <trkpt lat="{}" lon="{}">"#, lat, lon);
+        if let Some(altitude) = altitudes.get(i) {
+            let _ = write!(gpx, "<ele>{}</ele>", altitude);
+        }
+        let _ = writeln!(
+            gpx,
+            "<time>{}</time></trkpt>",
+            time.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+    }
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+
+    Some(gpx)
+}
+
+/// Retrieves streams of an activity.
+///
+/// Types the activity did not record are missing from the result.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `activity_id` - Identifier of the activity
+/// * `types` - Stream types to retrieve
+/// * `resolution` - Number of samples, or every sample if `None`
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if the request fails or the response cannot be parsed.
+pub(crate) fn get_activity_streams(
+    api: &Api,
+    activity_id: i64,
+    types: &[StreamType],
+    resolution: Option<Resolution>,
+) -> Result<Streams, StravaError> {
+    let keys: Vec<&str> = types.iter().map(|t| t.as_str()).collect();
+    let mut query = vec![
+        ("keys", keys.join(",")),
+        ("key_by_type", "true".to_string()),
+    ];
+    if let Some(resolution) = resolution {
+        query.push(("resolution", resolution.as_str().to_string()));
+    }
+
+    api.get(&format!("activities/{}/streams", activity_id), &query)
+}
@@ -0,0 +1,188 @@
+//! Strava uploads
+//!
+//! Posts FIT, TCX and GPX files, optionally gzip compressed, to the uploads
+//! endpoint. Strava processes uploads in the background, so the upload has to
+//! be polled until it turns into an activity or fails.
+//! Docs: https://developers.strava.com/docs/uploads/
+
+use reqwest::blocking::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use super::request::Api;
+use super::StravaError;
+
+/// First bytes of a gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Optional metadata of an upload
+#[derive(Debug, Clone, Default)]
+pub struct UploadOptions {
+    /// Name of the activity, Strava picks one if not set
+    pub name: Option<String>,
+    /// Description of the activity
+    pub description: Option<String>,
+    /// Whether the activity was done on a trainer
+    pub trainer: bool,
+    /// Whether the activity is a commute
+    pub commute: bool,
+    /// Identifier of the file, defaults to the file name
+    pub external_id: Option<String>,
+}
+
+/// An upload as reported by Strava
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Upload {
+    /// Identifier of the upload
+    pub id: i64,
+    /// Identifier of the uploaded file
+    pub external_id: Option<String>,
+    /// Processing status, e.g. `Your activity is still being processed.`
+    pub status: String,
+    /// Error message if processing failed
+    pub error: Option<String>,
+    /// Identifier of the created activity once processed
+    pub activity_id: Option<i64>,
+}
+
+/// Where an upload stands
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "outcome")]
+pub enum UploadOutcome {
+    /// Strava is still processing the file
+    Processing,
+    /// The file was turned into an activity
+    Created {
+        /// Identifier of the created activity
+        activity_id: i64,
+    },
+    /// The file matches an activity that already exists
+    Duplicate {
+        /// Identifier of the existing activity, if Strava reported it
+        activity_id: Option<i64>,
+    },
+    /// Strava could not process the file
+    Failed {
+        /// Error message of Strava
+        error: String,
+    },
+}
+
+impl Upload {
+    /// Returns where the upload stands, detecting duplicates from the error message.
+    pub fn outcome(&self) -> UploadOutcome {
+        if let Some(error) = &self.error {
+            return match error.find("duplicate of") {
+                Some(position) => UploadOutcome::Duplicate {
+                    activity_id: first_number(&error[position..]),
+                },
+                None => UploadOutcome::Failed {
+                    error: error.clone(),
+                },
+            };
+        }
+
+        match self.activity_id {
+            Some(activity_id) => UploadOutcome::Created { activity_id },
+            None => UploadOutcome::Processing,
+        }
+    }
+}
+
+/// Returns the first number in a text.
+fn first_number(text: &str) -> Option<i64> {
+    let digits: String = text
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Returns the Strava data type of a file, e.g. `fit.gz`.
+///
+/// The format is taken from the extension, ignoring any `.gz` suffix, and
+/// compression is detected from the content.
+fn data_type(path: &Path, contents: &[u8]) -> Result<String, StravaError> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let base_name = file_name.strip_suffix(".gz").unwrap_or(&file_name);
+
+    let format = ["fit", "tcx", "gpx"]
+        .into_iter()
+        .find(|format| base_name.ends_with(&format!(".{}", format)))
+        .ok_or_else(|| StravaError::Upload {
+            message: format!("Unsupported file type: {}", path.display()),
+            help: Some("Upload .fit, .tcx or .gpx files, optionally gzip compressed".to_string()),
+        })?;
+
+    if contents.starts_with(&GZIP_MAGIC) {
+        Ok(format!("{}.gz", format))
+    } else {
+        Ok(format.to_string())
+    }
+}
+
+/// Posts a file to the uploads endpoint.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `path` - Path of the FIT, TCX or GPX file
+/// * `options` - Metadata of the activity
+///
+/// # Errors
+///
+/// Returns `StravaError::Upload` if the file cannot be read or is not a
+/// supported type, and `StravaError::Api` if Strava rejects the upload.
+pub(crate) fn create(
+    api: &Api,
+    path: &Path,
+    options: &UploadOptions,
+) -> Result<Upload, StravaError> {
+    let contents = fs::read(path).map_err(|e| StravaError::Upload {
+        message: format!("Failed to read {}: {}", path.display(), e),
+        help: None,
+    })?;
+    let data_type = data_type(path, &contents)?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let external_id = options
+        .external_id
+        .clone()
+        .unwrap_or_else(|| file_name.clone());
+
+    let mut form = Form::new()
+        .part("file", Part::bytes(contents).file_name(file_name))
+        .text("data_type", data_type)
+        .text("external_id", external_id)
+        .text("trainer", u8::from(options.trainer).to_string())
+        .text("commute", u8::from(options.commute).to_string());
+    if let Some(name) = &options.name {
+        form = form.text("name", name.clone());
+    }
+    if let Some(description) = &options.description {
+        form = form.text("description", description.clone());
+    }
+
+    api.post_multipart("uploads", form)
+}
+
+/// Retrieves the current state of an upload.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Strava API
+/// * `id` - Identifier of the upload
+///
+/// # Errors
+///
+/// Returns `StravaError::Api` if the request fails or the response cannot be parsed.
+pub(crate) fn get(api: &Api, id: i64) -> Result<Upload, StravaError> {
+    api.get(&format!("uploads/{}", id), &[])
+}
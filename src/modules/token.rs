@@ -0,0 +1,352 @@
+//! Access token cache shared by the Strava and Withings sessions
+//!
+//! Tokens are kept in the config file of each service, in the format written
+//! by the upstream clients plus the time the access token expires. Once read,
+//! a token is cached for the whole process and only refreshed when it is about
+//! to expire. Both services rotate the refresh token on every refresh, so the
+//! new token is written atomically before the access token is handed out; a
+//! crash can then never leave a file holding a revoked refresh token.
+
+use chrono::Utc;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{fs, io};
+
+use super::files;
+
+/// Seconds before its expiry an access token is refreshed
+const EXPIRY_MARGIN: i64 = 300;
+
+/// Tokens read or refreshed by the process, by config file
+static TOKENS: Mutex<BTreeMap<PathBuf, Token>> = Mutex::new(BTreeMap::new());
+
+/// Tokens of a user as stored in the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Token {
+    /// Token sent with API requests
+    pub(crate) access_token: String,
+    /// Token used to obtain a new access token
+    pub(crate) refresh_token: String,
+    /// Unix time the access token expires, unknown for files written by the
+    /// upstream clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) expires_at: Option<i64>,
+}
+
+impl Token {
+    /// Returns whether the access token is valid for more than the expiry margin
+    fn is_fresh(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at - EXPIRY_MARGIN > Utc::now().timestamp())
+    }
+}
+
+/// Errors that can occur while reading, refreshing or saving a token
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum TokenError {
+    /// The config file exists but cannot be read
+    #[error("Failed to read the token file {path}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The config file does not hold tokens
+    #[error("The token file {path} is invalid")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The refreshed token cannot be saved
+    #[error("Failed to save the token file {path}")]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// The service refused to refresh the token
+    #[error("Failed to refresh the access token")]
+    Refresh(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Returns a valid access token for the user of a config file, refreshing it
+/// first if it expires within five minutes.
+///
+/// The lock on the cache is held while refreshing, so concurrent callers wait
+/// for the refreshed token instead of spending the rotated refresh token twice.
+///
+/// # Arguments
+///
+/// * `path` - Path of the config file holding the tokens
+/// * `refresh` - Exchanges a refresh token for a new token
+///
+/// # Returns
+///
+/// The access token, or `None` if the config file does not exist and the user
+/// has to authorize the application first.
+///
+/// # Errors
+///
+/// Returns a `TokenError` if the config file cannot be read or written, or the
+/// refresh fails.
+pub(crate) fn access_token<E>(
+    path: &Path,
+    refresh: impl FnOnce(&str) -> Result<Token, E>,
+) -> Result<Option<String>, TokenError>
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let mut tokens = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+
+    let token = match tokens.get(path) {
+        Some(token) => token.clone(),
+        None => match load(path)? {
+            Some(token) => token,
+            None => return Ok(None),
+        },
+    };
+
+    let token = if token.is_fresh() {
+        token
+    } else {
+        debug!("Refreshing the access token of {}", path.display());
+        let token = refresh(&token.refresh_token).map_err(|e| TokenError::Refresh(e.into()))?;
+        save(path, &token)?;
+        token
+    };
+
+    let access_token = token.access_token.clone();
+    tokens.insert(path.to_path_buf(), token);
+    Ok(Some(access_token))
+}
+
+/// Saves the first tokens of a user, obtained by authorizing the application,
+/// and caches them for the process.
+///
+/// # Arguments
+///
+/// * `path` - Path of the config file to hold the tokens
+/// * `token` - Tokens returned for the authorization code
+///
+/// # Returns
+///
+/// The access token.
+///
+/// # Errors
+///
+/// Returns `TokenError::Write` if the config file cannot be written.
+pub(crate) fn store(path: &Path, token: Token) -> Result<String, TokenError> {
+    let mut tokens = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+
+    save(path, &token)?;
+    let access_token = token.access_token.clone();
+    tokens.insert(path.to_path_buf(), token);
+    Ok(access_token)
+}
+
+/// Reads the tokens of a config file, or `None` if it does not exist.
+fn load(path: &Path) -> Result<Option<Token>, TokenError> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => {
+            return Err(TokenError::Read {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    };
+
+    serde_json::from_slice(&contents)
+        .map(Some)
+        .map_err(|source| TokenError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Writes the tokens to a config file readable by its owner only.
+fn save(path: &Path, token: &Token) -> Result<(), TokenError> {
+    let contents = serde_json::to_vec_pretty(token).map_err(|source| TokenError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    files::write_private(path, contents).map_err(|source| TokenError::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::convert::Infallible;
+
+    /// Returns a token file path of its own for a test, as tokens are cached by path
+    fn token_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fit-connect-rs-token-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.json", name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn token(access_token: &str, expires_in: Option<i64>) -> Token {
+        Token {
+            access_token: access_token.to_string(),
+            refresh_token: format!("refresh-{}", access_token),
+            expires_at: expires_in.map(|seconds| Utc::now().timestamp() + seconds),
+        }
+    }
+
+    fn write(path: &Path, token: &Token) {
+        fs::write(path, serde_json::to_vec(token).unwrap()).unwrap();
+    }
+
+    /// Refreshes to `new`, counting the calls and checking the refresh token sent
+    fn refresher<'a>(
+        calls: &'a Cell<usize>,
+        expected_refresh_token: &'a str,
+        new: Token,
+    ) -> impl FnOnce(&str) -> Result<Token, Infallible> + 'a {
+        move |refresh_token| {
+            assert_eq!(refresh_token, expected_refresh_token);
+            calls.set(calls.get() + 1);
+            Ok(new)
+        }
+    }
+
+    #[test]
+    fn missing_file_needs_authorization() {
+        let path = token_file("missing");
+        let calls = Cell::new(0);
+
+        let access_token = access_token(&path, refresher(&calls, "", token("new", Some(3600))));
+        assert_eq!(access_token.unwrap(), None);
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn fresh_token_is_used_as_is() {
+        let path = token_file("fresh");
+        write(&path, &token("old", Some(EXPIRY_MARGIN + 60)));
+        let calls = Cell::new(0);
+
+        let access_token = access_token(
+            &path,
+            refresher(&calls, "refresh-old", token("new", Some(3600))),
+        );
+        assert_eq!(access_token.unwrap().as_deref(), Some("old"));
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn token_within_the_margin_is_refreshed_and_saved() {
+        let path = token_file("expiring");
+        write(&path, &token("old", Some(EXPIRY_MARGIN - 60)));
+        let calls = Cell::new(0);
+
+        let access_token = access_token(
+            &path,
+            refresher(&calls, "refresh-old", token("new", Some(3600))),
+        );
+        assert_eq!(access_token.unwrap().as_deref(), Some("new"));
+        assert_eq!(calls.get(), 1);
+
+        let saved = load(&path).unwrap().unwrap();
+        assert_eq!(saved.access_token, "new");
+        assert_eq!(saved.refresh_token, "refresh-new");
+        assert!(saved.is_fresh());
+    }
+
+    #[test]
+    fn refreshed_token_is_cached_for_the_process() {
+        let path = token_file("cached");
+        write(&path, &token("old", Some(-60)));
+        let calls = Cell::new(0);
+
+        access_token(
+            &path,
+            refresher(&calls, "refresh-old", token("new", Some(3600))),
+        )
+        .unwrap();
+        // The file no longer matters once the token is cached
+        fs::remove_file(&path).unwrap();
+        let access_token = access_token(
+            &path,
+            refresher(&calls, "refresh-new", token("newer", Some(3600))),
+        );
+
+        assert_eq!(access_token.unwrap().as_deref(), Some("new"));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn token_without_expiry_is_refreshed() {
+        let path = token_file("upstream");
+        write(&path, &token("old", None));
+        let calls = Cell::new(0);
+
+        let access_token = access_token(
+            &path,
+            refresher(&calls, "refresh-old", token("new", Some(3600))),
+        );
+        assert_eq!(access_token.unwrap().as_deref(), Some("new"));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn failed_refresh_keeps_the_file() {
+        let path = token_file("refused");
+        write(&path, &token("old", Some(-60)));
+
+        let result = access_token(&path, |_| Err("invalid_grant"));
+        assert!(matches!(result, Err(TokenError::Refresh(_))));
+        assert_eq!(load(&path).unwrap().unwrap().access_token, "old");
+    }
+
+    #[test]
+    fn invalid_file_is_reported() {
+        let path = token_file("invalid");
+        fs::write(&path, "{}").unwrap();
+        let calls = Cell::new(0);
+
+        let result = access_token(&path, refresher(&calls, "", token("new", Some(3600))));
+        assert!(matches!(result, Err(TokenError::Parse { .. })));
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn stored_token_is_cached() {
+        let path = token_file("stored");
+
+        assert_eq!(store(&path, token("first", Some(3600))).unwrap(), "first");
+        let calls = Cell::new(0);
+        let access_token = access_token(&path, refresher(&calls, "", token("new", Some(3600))));
+
+        assert_eq!(access_token.unwrap().as_deref(), Some("first"));
+        assert_eq!(calls.get(), 0);
+        assert_eq!(load(&path).unwrap().unwrap().access_token, "first");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_tokens_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+        // A profile's token directory does not exist until its first token
+        let dir = token_file("private").with_extension("d");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("strava.json");
+
+        store(&path, token("first", Some(3600))).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(&dir), 0o700);
+    }
+}
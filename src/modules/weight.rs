@@ -0,0 +1,197 @@
+//! Weight readings and the policies used to pick a single weight from them.
+//!
+//! A measurement source can return several weigh-ins for a period. The
+//! [`WeightPolicy`] decides which value is reported or synced, so the result
+//! does not depend on the order the service lists its readings in.
+
+use chrono::{DateTime, Local, Utc};
+
+/// A single weigh-in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightReading {
+    /// Time the weight was taken
+    pub taken_at: DateTime<Utc>,
+    /// Weight in kilograms
+    pub kg: f64,
+}
+
+/// Default smoothing factor of [`WeightPolicy::Trend`]
+pub const DEFAULT_SMOOTHING: f64 = 0.1;
+
+/// Returns whether a smoothing factor is in (0, 1], the range the trend is
+/// defined for.
+pub fn is_valid_smoothing(smoothing: f64) -> bool {
+    smoothing > 0.0 && smoothing <= 1.0
+}
+
+/// How a single weight is selected from the readings of a period
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WeightPolicy {
+    /// The most recent reading
+    #[default]
+    Latest,
+    /// The first reading of the most recent day with a reading, i.e. the morning weight
+    Morning,
+    /// The mean of all readings
+    Mean,
+    /// The median of all readings
+    Median,
+    /// The exponentially smoothed trend of all readings
+    Trend {
+        /// Weight given to each new reading, between 0 and 1
+        smoothing: f64,
+    },
+}
+
+impl WeightPolicy {
+    /// Selects a weight from a set of readings.
+    ///
+    /// # Arguments
+    ///
+    /// * `readings` - Weigh-ins of the period, in any order
+    ///
+    /// # Returns
+    ///
+    /// The selected weight in kilograms, or `None` if there are no readings.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chrono::DateTime;
+    /// use fit_connect_rs::modules::weight::{WeightPolicy, WeightReading};
+    ///
+    /// let readings = [80.0, 81.0, 85.0].map(|kg| WeightReading {
+    ///     taken_at: DateTime::from_timestamp(kg as i64 * 3600, 0).unwrap(),
+    ///     kg,
+    /// });
+    /// assert_eq!(WeightPolicy::Latest.select(&readings), Some(85.0));
+    /// assert_eq!(WeightPolicy::Median.select(&readings), Some(81.0));
+    /// ```
+    pub fn select(self, readings: &[WeightReading]) -> Option<f64> {
+        let mut sorted = readings.to_vec();
+        sorted.sort_by_key(|r| r.taken_at);
+
+        match self {
+            WeightPolicy::Latest => sorted.last().map(|r| r.kg),
+            WeightPolicy::Morning => {
+                let last_day = sorted.last()?.taken_at.with_timezone(&Local).date_naive();
+                sorted
+                    .iter()
+                    .find(|r| r.taken_at.with_timezone(&Local).date_naive() == last_day)
+                    .map(|r| r.kg)
+            }
+            WeightPolicy::Mean => {
+                let total: f64 = sorted.iter().map(|r| r.kg).sum();
+                (!sorted.is_empty()).then(|| total / sorted.len() as f64)
+            }
+            WeightPolicy::Median => {
+                let mut weights: Vec<f64> = sorted.iter().map(|r| r.kg).collect();
+                weights.sort_by(f64::total_cmp);
+                let middle = weights.len() / 2;
+                match weights.len() {
+                    0 => None,
+                    n if n % 2 == 0 => Some((weights[middle - 1] + weights[middle]) / 2.0),
+                    _ => Some(weights[middle]),
+                }
+            }
+            WeightPolicy::Trend { smoothing } => {
+                let mut readings = sorted.iter().map(|r| r.kg);
+                let first = readings.next()?;
+                Some(readings.fold(first, |trend, kg| trend + smoothing * (kg - trend)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Reading taken at a local time in June, away from daylight saving changes
+    fn reading(day: u32, hour: u32, kg: f64) -> WeightReading {
+        WeightReading {
+            taken_at: Local
+                .with_ymd_and_hms(2024, 6, day, hour, 0, 0)
+                .single()
+                .unwrap()
+                .with_timezone(&Utc),
+            kg,
+        }
+    }
+
+    const POLICIES: [WeightPolicy; 5] = [
+        WeightPolicy::Latest,
+        WeightPolicy::Morning,
+        WeightPolicy::Mean,
+        WeightPolicy::Median,
+        WeightPolicy::Trend { smoothing: 0.5 },
+    ];
+
+    #[test]
+    fn no_readings_select_nothing() {
+        for policy in POLICIES {
+            assert_eq!(policy.select(&[]), None, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn morning_is_the_first_reading_of_the_last_day() {
+        let readings = [
+            reading(11, 21, 83.0),
+            reading(11, 6, 81.0),
+            reading(10, 6, 80.0),
+            reading(11, 13, 82.0),
+        ];
+
+        assert_eq!(WeightPolicy::Morning.select(&readings), Some(81.0));
+    }
+
+    #[test]
+    fn morning_uses_the_last_day_even_without_an_early_reading() {
+        let readings = [reading(10, 6, 80.0), reading(11, 22, 84.0)];
+
+        assert_eq!(WeightPolicy::Morning.select(&readings), Some(84.0));
+    }
+
+    #[test]
+    fn trend_follows_the_readings_in_time_order() {
+        let in_order = [
+            reading(10, 6, 80.0),
+            reading(11, 6, 84.0),
+            reading(12, 6, 82.0),
+        ];
+        let shuffled = [in_order[2], in_order[0], in_order[1]];
+        let policy = WeightPolicy::Trend { smoothing: 0.5 };
+
+        // 80, then 80 + 0.5 * (84 - 80) = 82, then 82 + 0.5 * (82 - 82) = 82
+        assert_eq!(policy.select(&in_order), Some(82.0));
+        assert_eq!(policy.select(&shuffled), Some(82.0));
+
+        let reversed = [in_order[2], in_order[1], in_order[0]];
+        assert_eq!(policy.select(&reversed), Some(82.0));
+    }
+
+    #[test]
+    fn trend_of_one_reading_is_the_reading() {
+        let policy = WeightPolicy::Trend {
+            smoothing: DEFAULT_SMOOTHING,
+        };
+
+        assert_eq!(policy.select(&[reading(10, 6, 80.0)]), Some(80.0));
+    }
+
+    #[test]
+    fn latest_mean_and_median_ignore_the_order() {
+        let readings = [
+            reading(12, 6, 82.0),
+            reading(10, 6, 80.0),
+            reading(11, 6, 86.0),
+            reading(9, 6, 84.0),
+        ];
+
+        assert_eq!(WeightPolicy::Latest.select(&readings), Some(82.0));
+        assert_eq!(WeightPolicy::Mean.select(&readings), Some(83.0));
+        assert_eq!(WeightPolicy::Median.select(&readings), Some(83.0));
+    }
+}
@@ -0,0 +1,111 @@
+//! Withings daily activity summaries
+//!
+//! Calls the `getactivity` action of the measure v2 API, which returns one
+//! aggregated record per day for watches and trackers.
+//! Docs: https://developer.withings.com/api-reference/#tag/measure/operation/measurev2-getactivity
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::{self, Api};
+use super::{Window, WithingsError};
+
+/// Fields requested from the activity API
+const DATA_FIELDS: &str = "steps,distance,elevation,soft,moderate,intense,active,calories,totalcalories,hr_average,hr_min,hr_max";
+
+/// Activity totals of a single day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivitySummary {
+    /// Day the summary covers
+    pub date: NaiveDate,
+    /// Timezone of the day
+    pub timezone: Option<String>,
+    /// Identifier of the device that recorded the activity, if any
+    #[serde(rename(deserialize = "deviceid"))]
+    pub device_id: Option<String>,
+    /// Model name of the device that recorded the activity, if any
+    pub model: Option<String>,
+    /// Number of steps
+    pub steps: Option<i64>,
+    /// Distance travelled (m)
+    pub distance: Option<f64>,
+    /// Elevation climbed (m)
+    pub elevation: Option<f64>,
+    /// Time spent in soft activities (s)
+    pub soft: Option<i64>,
+    /// Time spent in moderate activities (s)
+    pub moderate: Option<i64>,
+    /// Time spent in intense activities (s)
+    pub intense: Option<i64>,
+    /// Time spent in any activity (s)
+    pub active: Option<i64>,
+    /// Active calories burned (kcal)
+    pub calories: Option<f64>,
+    /// Total calories burned including the basal metabolic rate (kcal)
+    #[serde(rename(deserialize = "totalcalories"))]
+    pub total_calories: Option<f64>,
+    /// Average heart rate (bpm)
+    pub hr_average: Option<i64>,
+    /// Minimum heart rate (bpm)
+    pub hr_min: Option<i64>,
+    /// Maximum heart rate (bpm)
+    pub hr_max: Option<i64>,
+}
+
+impl ActivitySummary {
+    /// Returns the minutes spent in moderate and intense activities
+    pub fn intensity_minutes(&self) -> i64 {
+        (self.moderate.unwrap_or_default() + self.intense.unwrap_or_default()) / 60
+    }
+}
+
+/// Body of the activity response
+#[derive(Deserialize)]
+struct ActivityBody {
+    activities: Vec<ActivitySummary>,
+    #[serde(default, deserialize_with = "request::deserialize_more")]
+    more: bool,
+    #[serde(default)]
+    offset: i64,
+}
+
+/// Retrieves the daily activity summaries of a window, following the API paging.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Withings API
+/// * `window` - Period of time to fetch summaries for
+///
+/// # Returns
+///
+/// One summary per day, in chronological order.
+///
+/// # Errors
+///
+/// Returns `WithingsError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn get_activity(
+    api: &Api,
+    window: Window,
+) -> Result<Vec<ActivitySummary>, WithingsError> {
+    let mut query = HashMap::new();
+    query.insert("action", "getactivity".to_string());
+    query.insert("data_fields", DATA_FIELDS.to_string());
+    window.apply_ymd(&mut query);
+
+    let mut summaries = Vec::new();
+    let mut previous = None;
+    while let Some(body) = api.get_body::<ActivityBody>("v2/measure", &query)? {
+        summaries.extend(body.activities);
+
+        let Some(offset) = request::next_offset("v2/measure", body.more, body.offset, previous)
+        else {
+            break;
+        };
+        query.insert("offset", offset.to_string());
+        previous = Some(offset);
+    }
+
+    summaries.sort_by_key(|summary| summary.date);
+    Ok(summaries)
+}
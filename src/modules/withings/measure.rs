@@ -0,0 +1,490 @@
+//! Decoded Withings body measurements
+//!
+//! The Withings measure API encodes every value as an integer mantissa and a
+//! power of ten exponent (`value * 10^unit`). This module calls the measure
+//! endpoint and decodes each value into a real-world quantity, keeping the
+//! timestamp, device and attribution of the measure group it came from.
+//! Measure groups taken at the same time are merged into a single
+//! [`BodyComposition`] record so one weigh-in is reported once.
+//! Docs: https://developer.withings.com/api-reference/#tag/measure
+
+use chrono::{DateTime, Utc};
+use log::trace;
+use serde::{Deserialize, Serialize};
+use withings_rs::api::measure::MeasurementParams;
+use withings_rs::models::MeasureType;
+
+use super::request::{self, Api};
+use super::WeightError;
+
+/// Describes how a measure group was captured and whether it belongs to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribution {
+    /// Captured by a device and known to belong to the user
+    Device,
+    /// Captured by a device but may belong to another user of the device
+    Ambiguous,
+    /// Entered manually by the user
+    Manual,
+    /// Entered manually during account creation and may not be accurate
+    ManualCreation,
+    /// Best value computed by a blood pressure monitor from several readings
+    Auto,
+    /// Detected by a device and confirmed by the user
+    Confirmed,
+    /// Attribution code not known to this version
+    Other(i64),
+}
+
+impl From<i64> for Attribution {
+    fn from(code: i64) -> Self {
+        match code {
+            0 | 8 => Attribution::Device,
+            1 => Attribution::Ambiguous,
+            2 => Attribution::Manual,
+            4 => Attribution::ManualCreation,
+            5 => Attribution::Auto,
+            7 => Attribution::Confirmed,
+            other => Attribution::Other(other),
+        }
+    }
+}
+
+/// A single decoded measurement
+#[derive(Debug, Clone, Serialize)]
+pub struct Measurement {
+    /// Withings measure type code, e.g. 1 for weight
+    pub measure_type: i64,
+    /// Raw integer value as returned by the API
+    pub value: i64,
+    /// Power of ten exponent applied to `value`
+    pub unit: i64,
+    /// Real-world quantity (`value * 10^unit`) in the unit of the measure type, e.g. kg for weight
+    pub quantity: f64,
+    /// Time the measurement was taken
+    pub taken_at: DateTime<Utc>,
+    /// Identifier of the device that took the measurement, if any
+    pub device_id: Option<String>,
+    /// Model name of the device that took the measurement, if any
+    pub model: Option<String>,
+    /// How the measurement was captured
+    pub attribution: Attribution,
+}
+
+/// Decodes a Withings `value` and `unit` pair into a real-world quantity.
+///
+/// # Examples
+///
+/// ```rust
+/// use fit_connect_rs::modules::withings::measure::decode_value;
+///
+/// assert_eq!(decode_value(80450, -3), 80.45);
+/// assert_eq!(decode_value(179, -2), 1.79);
+/// ```
+pub fn decode_value(value: i64, unit: i64) -> f64 {
+    value as f64 * 10f64.powi(unit as i32)
+}
+
+/// Every body measure type fetched for a body composition record
+pub const BODY_MEASURE_TYPES: [MeasureType; 14] = [
+    MeasureType::Weight,
+    MeasureType::Height,
+    MeasureType::FatFreeMass,
+    MeasureType::FatRatio,
+    MeasureType::FatMassWeight,
+    MeasureType::DiastolicBloodPressure,
+    MeasureType::SystolicBloodPressure,
+    MeasureType::HeartPulse,
+    MeasureType::Temperature,
+    MeasureType::Sp02,
+    MeasureType::BodyTemperature,
+    MeasureType::MuscleMass,
+    MeasureType::Hydration,
+    MeasureType::BoneMass,
+];
+
+/// Joins measure types into the comma separated list expected by the API.
+pub fn meastypes(types: &[MeasureType]) -> String {
+    types
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Every measurement captured together by a device or entered by the user
+#[derive(Debug, Clone, Serialize)]
+pub struct MeasureGroup {
+    /// Unique identifier of the group
+    pub id: i64,
+    /// Time the measurements were taken
+    pub taken_at: DateTime<Utc>,
+    /// Identifier of the device that took the measurements, if any
+    pub device_id: Option<String>,
+    /// Model name of the device that took the measurements, if any
+    pub model: Option<String>,
+    /// How the measurements were captured
+    pub attribution: Attribution,
+    /// Decoded measurements of the group
+    pub measures: Vec<Measurement>,
+}
+
+/// All body measurements of a single weigh-in or check
+///
+/// Quantities are `None` when the device did not report them.
+#[derive(Debug, Clone, Serialize)]
+pub struct BodyComposition {
+    /// Time the measurements were taken
+    pub taken_at: DateTime<Utc>,
+    /// Identifier of the device that took the measurements, if any
+    pub device_id: Option<String>,
+    /// Model name of the device that took the measurements, if any
+    pub model: Option<String>,
+    /// How the measurements were captured
+    pub attribution: Attribution,
+    /// Weight (kg)
+    pub weight: Option<f64>,
+    /// Height (m)
+    pub height: Option<f64>,
+    /// Fat ratio (%)
+    pub fat_ratio: Option<f64>,
+    /// Fat mass (kg)
+    pub fat_mass: Option<f64>,
+    /// Fat free mass (kg)
+    pub fat_free_mass: Option<f64>,
+    /// Muscle mass (kg)
+    pub muscle_mass: Option<f64>,
+    /// Bone mass (kg)
+    pub bone_mass: Option<f64>,
+    /// Hydration (kg)
+    pub hydration: Option<f64>,
+    /// Heart pulse (bpm)
+    pub heart_pulse: Option<f64>,
+    /// SpO2 (%)
+    pub spo2: Option<f64>,
+    /// Temperature (C)
+    pub temperature: Option<f64>,
+    /// Body temperature (C)
+    pub body_temperature: Option<f64>,
+    /// Systolic blood pressure (mmHg)
+    pub systolic_blood_pressure: Option<f64>,
+    /// Diastolic blood pressure (mmHg)
+    pub diastolic_blood_pressure: Option<f64>,
+}
+
+impl BodyComposition {
+    /// Creates an empty record for a measure group.
+    fn new(group: &MeasureGroup) -> Self {
+        BodyComposition {
+            taken_at: group.taken_at,
+            device_id: group.device_id.clone(),
+            model: group.model.clone(),
+            attribution: group.attribution,
+            weight: None,
+            height: None,
+            fat_ratio: None,
+            fat_mass: None,
+            fat_free_mass: None,
+            muscle_mass: None,
+            bone_mass: None,
+            hydration: None,
+            heart_pulse: None,
+            spo2: None,
+            temperature: None,
+            body_temperature: None,
+            systolic_blood_pressure: None,
+            diastolic_blood_pressure: None,
+        }
+    }
+
+    /// Stores a measurement in the field matching its type, ignoring unknown types.
+    fn set(&mut self, measurement: &Measurement) {
+        let field = match measurement.measure_type {
+            t if t == MeasureType::Weight as i64 => &mut self.weight,
+            t if t == MeasureType::Height as i64 => &mut self.height,
+            t if t == MeasureType::FatRatio as i64 => &mut self.fat_ratio,
+            t if t == MeasureType::FatMassWeight as i64 => &mut self.fat_mass,
+            t if t == MeasureType::FatFreeMass as i64 => &mut self.fat_free_mass,
+            t if t == MeasureType::MuscleMass as i64 => &mut self.muscle_mass,
+            t if t == MeasureType::BoneMass as i64 => &mut self.bone_mass,
+            t if t == MeasureType::Hydration as i64 => &mut self.hydration,
+            t if t == MeasureType::HeartPulse as i64 => &mut self.heart_pulse,
+            t if t == MeasureType::Sp02 as i64 => &mut self.spo2,
+            t if t == MeasureType::Temperature as i64 => &mut self.temperature,
+            t if t == MeasureType::BodyTemperature as i64 => &mut self.body_temperature,
+            t if t == MeasureType::SystolicBloodPressure as i64 => {
+                &mut self.systolic_blood_pressure
+            }
+            t if t == MeasureType::DiastolicBloodPressure as i64 => {
+                &mut self.diastolic_blood_pressure
+            }
+            _ => return,
+        };
+        *field = Some(measurement.quantity);
+    }
+
+    /// Merges measure groups taken at the same time on the same device into
+    /// one record each, in the order the groups were first seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `groups` - Decoded measure groups
+    pub fn from_groups(groups: &[MeasureGroup]) -> Vec<BodyComposition> {
+        let mut records: Vec<BodyComposition> = Vec::new();
+
+        for group in groups {
+            let index = records
+                .iter()
+                .position(|r| r.taken_at == group.taken_at && r.device_id == group.device_id)
+                .unwrap_or_else(|| {
+                    records.push(BodyComposition::new(group));
+                    records.len() - 1
+                });
+
+            for measurement in &group.measures {
+                records[index].set(measurement);
+            }
+        }
+
+        records
+    }
+}
+
+/// Body of the measure response
+#[derive(Deserialize)]
+struct MeasureBody {
+    measuregrps: Vec<RawMeasureGroup>,
+    /// Set when more groups are available past `offset`
+    #[serde(default, deserialize_with = "request::deserialize_more")]
+    more: bool,
+    /// Offset to request the next page with
+    #[serde(default)]
+    offset: i64,
+}
+
+/// Measure group as returned by the API
+#[derive(Deserialize)]
+struct RawMeasureGroup {
+    grpid: i64,
+    attrib: i64,
+    date: i64,
+    deviceid: Option<String>,
+    model: Option<String>,
+    measures: Vec<RawMeasure>,
+}
+
+/// Measure as returned by the API
+#[derive(Deserialize)]
+struct RawMeasure {
+    value: i64,
+    #[serde(rename = "type")]
+    measure_type: i64,
+    unit: i64,
+}
+
+impl RawMeasureGroup {
+    /// Decodes every measure of the group, copying the group metadata onto each one.
+    fn decode(self) -> MeasureGroup {
+        let taken_at = DateTime::from_timestamp(self.date, 0).unwrap_or_default();
+        let attribution = Attribution::from(self.attrib);
+
+        let measures = self
+            .measures
+            .into_iter()
+            .map(|measure| Measurement {
+                measure_type: measure.measure_type,
+                value: measure.value,
+                unit: measure.unit,
+                quantity: decode_value(measure.value, measure.unit),
+                taken_at,
+                device_id: self.deviceid.clone(),
+                model: self.model.clone(),
+                attribution,
+            })
+            .collect();
+
+        MeasureGroup {
+            id: self.grpid,
+            taken_at,
+            device_id: self.deviceid,
+            model: self.model,
+            attribution,
+            measures,
+        }
+    }
+}
+
+/// Retrieves and decodes measure groups from the Withings measure API.
+///
+/// The `meastype` parameter may hold a single type or a comma separated list
+/// built with [`meastypes`]. Pages are requested until the API reports no
+/// more groups, updating `params.offset` as it goes.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Withings API
+/// * `params` - The measure request parameters
+///
+/// # Returns
+///
+/// Every decoded measure group of every page, in chronological order.
+///
+/// # Errors
+///
+/// Returns `WeightError::Measurement` if a request fails, the API reports a
+/// non-zero status or a response cannot be parsed.
+pub(crate) fn get_measure_groups(
+    api: &Api,
+    params: &mut MeasurementParams,
+) -> Result<Vec<MeasureGroup>, WeightError> {
+    let mut groups = Vec::new();
+    let mut previous = None;
+
+    loop {
+        let mut query_params = params.to_query_params();
+        // meastypes accepts a single type as well as a list
+        if let Some(types) = query_params.remove("meastype") {
+            query_params.insert("meastypes", types);
+        }
+
+        let body: Option<MeasureBody> = api
+            .get_body("measure", &query_params)
+            .map_err(|e| WeightError::Measurement(e.to_string()))?;
+
+        let Some(body) = body else {
+            break;
+        };
+        groups.extend(body.measuregrps.into_iter().map(RawMeasureGroup::decode));
+
+        let Some(offset) = request::next_offset("measure", body.more, body.offset, previous) else {
+            break;
+        };
+        trace!("Measure API has more groups after offset {}", offset);
+        params.offset = Some(offset.to_string());
+        previous = Some(offset);
+    }
+
+    groups.sort_by_key(|group| (group.taken_at, group.id));
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEIGHT: i64 = MeasureType::Weight as i64;
+    const HEIGHT: i64 = MeasureType::Height as i64;
+    const FAT_RATIO: i64 = MeasureType::FatRatio as i64;
+    const HEART_PULSE: i64 = MeasureType::HeartPulse as i64;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    fn group(
+        id: i64,
+        date: i64,
+        device: Option<&str>,
+        measures: &[(i64, i64, i64)],
+    ) -> MeasureGroup {
+        RawMeasureGroup {
+            grpid: id,
+            attrib: 0,
+            date,
+            deviceid: device.map(str::to_string),
+            model: None,
+            measures: measures
+                .iter()
+                .map(|(measure_type, value, unit)| RawMeasure {
+                    value: *value,
+                    measure_type: *measure_type,
+                    unit: *unit,
+                })
+                .collect(),
+        }
+        .decode()
+    }
+
+    #[test]
+    fn decodes_negative_exponents() {
+        assert_close(decode_value(80450, -3), 80.45);
+        assert_close(decode_value(179, -2), 1.79);
+        assert_close(decode_value(-5, -1), -0.5);
+        assert_close(decode_value(1, -9), 0.000_000_001);
+    }
+
+    #[test]
+    fn decodes_zero_and_positive_exponents() {
+        assert_close(decode_value(72, 0), 72.0);
+        assert_close(decode_value(12, 3), 12_000.0);
+        assert_close(decode_value(0, -3), 0.0);
+    }
+
+    #[test]
+    fn decodes_a_raw_group() {
+        let raw: RawMeasureGroup = serde_json::from_str(
+            r#"{"grpid": 7, "attrib": 2, "date": 1709280000, "deviceid": null,
+                "model": null, "measures": [{"value": 82400, "type": 1, "unit": -3}]}"#,
+        )
+        .unwrap();
+
+        let group = raw.decode();
+        assert_eq!(group.id, 7);
+        assert_eq!(group.attribution, Attribution::Manual);
+        assert_eq!(group.taken_at.timestamp(), 1709280000);
+        assert_eq!(group.measures[0].value, 82400);
+        assert_eq!(group.measures[0].unit, -3);
+        assert_close(group.measures[0].quantity, 82.4);
+        assert_eq!(group.measures[0].attribution, Attribution::Manual);
+    }
+
+    #[test]
+    fn merges_groups_of_the_same_weigh_in() {
+        let groups = [
+            group(1, 1000, Some("scale"), &[(WEIGHT, 82400, -3)]),
+            group(2, 1000, Some("scale"), &[(FAT_RATIO, 215, -1)]),
+        ];
+
+        let records = BodyComposition::from_groups(&groups);
+        assert_eq!(records.len(), 1);
+        assert_close(records[0].weight.unwrap(), 82.4);
+        assert_close(records[0].fat_ratio.unwrap(), 21.5);
+        assert_eq!(records[0].height, None);
+    }
+
+    #[test]
+    fn keeps_other_times_and_devices_apart() {
+        let groups = [
+            group(1, 1000, Some("scale"), &[(WEIGHT, 82400, -3)]),
+            group(2, 1000, Some("watch"), &[(HEART_PULSE, 61, 0)]),
+            group(3, 2000, Some("scale"), &[(WEIGHT, 82100, -3)]),
+            group(4, 1000, None, &[(HEIGHT, 179, -2)]),
+        ];
+
+        let records = BodyComposition::from_groups(&groups);
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].device_id.as_deref(), Some("scale"));
+        assert_eq!(records[1].device_id.as_deref(), Some("watch"));
+        assert_close(records[1].heart_pulse.unwrap(), 61.0);
+        assert_eq!(records[1].weight, None);
+        assert_eq!(records[2].taken_at.timestamp(), 2000);
+        assert_eq!(records[3].device_id, None);
+        assert_close(records[3].height.unwrap(), 1.79);
+    }
+
+    #[test]
+    fn ignores_unknown_measure_types() {
+        let mut groups = [group(1, 1000, None, &[(WEIGHT, 82400, -3)])];
+        groups[0].measures[0].measure_type = 9999;
+
+        let records = BodyComposition::from_groups(&groups);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].weight, None);
+    }
+}
@@ -0,0 +1,745 @@
+//! Withings API integration module for retrieving health data
+//!
+//! This module provides functionality to authenticate with the Withings API
+//! and retrieve body measurements, daily activity and sleep for specified dates.
+//! All calls go through a [`WithingsSession`].
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, Utc};
+use miette::{Context, IntoDiagnostic, Result};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::modules::config::{self, Config, ServiceConfig};
+use crate::modules::oauth::{self, OauthError};
+use crate::modules::provider::{MeasurementSource, WorkoutSource};
+use crate::modules::token::{self, Token};
+use crate::modules::weight::WeightReading;
+use crate::modules::workout::Workout;
+use request::Api;
+use withings_rs::{
+    api::measure::MeasurementParams,
+    models::{meas::CategoryType, MeasureType},
+};
+
+pub mod activity;
+pub mod measure;
+mod request;
+pub mod sleep;
+pub mod workouts;
+
+pub use activity::ActivitySummary;
+pub use measure::{Attribution, BodyComposition, MeasureGroup, Measurement};
+pub use sleep::{SleepSegment, SleepState, SleepSummary};
+pub use workouts::WithingsWorkout;
+
+/// Errors that can occur during Withings API operations
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum WithingsError {
+    /// Represents configuration-related errors
+    #[error("Configuration error: {message}")]
+    #[diagnostic(code(withings::config::invalid))]
+    Config {
+        /// Description of the configuration error
+        message: String,
+        /// Helpful suggestion to resolve the error
+        #[help]
+        help: String,
+    },
+
+    /// The user could not authorize the application
+    #[error("Authentication failed")]
+    #[diagnostic(code(withings::auth::failed))]
+    Authentication {
+        /// The underlying error that caused the authentication failure
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+        /// Helpful message for resolving the authentication issue
+        #[help]
+        help: Option<String>,
+    },
+
+    /// The user has not authorized the application yet
+    #[error("Not authorized with Withings: no tokens in {}", path.display())]
+    #[diagnostic(
+        code(withings::auth::missing),
+        help("Authorize the application with `fit-connect-rs withings --register`")
+    )]
+    NotAuthorized {
+        /// Path of the token file
+        path: PathBuf,
+    },
+
+    /// API-related errors, such as failed requests or invalid responses
+    #[error("API error: {message}")]
+    #[diagnostic(code(withings::api::error))]
+    Api {
+        /// Description of the API error
+        message: String,
+        /// Additional context about the API call
+        #[source_code]
+        src: Option<String>,
+    },
+}
+
+/// Errors that can occur during body measurement operations
+#[derive(thiserror::Error, Debug, miette::Diagnostic)]
+pub enum WeightError {
+    /// Authentication-related errors
+    #[error("Authentication error: {0}")]
+    #[diagnostic(code(withings::weight::auth))]
+    Auth(String),
+    /// Measurement retrieval errors
+    #[error("Measurement error: {0}")]
+    #[diagnostic(code(withings::weight::measurement))]
+    Measurement(String),
+    /// No measurements found for the requested period
+    #[error("No measurements available")]
+    #[diagnostic(code(withings::weight::empty))]
+    NoMeasurements,
+}
+
+/// Body of the OAuth token response
+#[derive(Deserialize)]
+struct OauthBody {
+    access_token: String,
+    refresh_token: String,
+    /// Seconds the access token is valid for
+    expires_in: i64,
+}
+
+/// A connection to the Withings API for a single user.
+///
+/// The session holds the application credentials, the file caching the
+/// user's tokens, the API URLs and an HTTP client whose connections are reused by every
+/// request. It is the entry point for every Withings API call; reuse one
+/// session for a whole run.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fit_connect_rs::modules::withings::get_day_before_timestamp;
+/// use fit_connect_rs::WithingsSession;
+///
+/// let session = WithingsSession::from_env()?;
+/// let weight = session.get_weight_by_date(get_day_before_timestamp(1).to_string())?;
+/// println!("Weight: {} kg", weight.quantity);
+/// # Ok::<(), miette::Report>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct WithingsSession {
+    /// Client ID of the Withings application
+    client_id: String,
+    /// Client secret of the Withings application
+    client_secret: String,
+    /// Path of the file caching the tokens of the user
+    config_file: PathBuf,
+    /// Base URL of the API
+    api_url: String,
+    /// URL of the OAuth token endpoint
+    token_url: String,
+    /// URL of the OAuth authorization page
+    auth_url: String,
+    /// Comma separated OAuth scopes requested when authorizing
+    scope: String,
+    /// Local port the OAuth redirect is received on
+    redirect_port: u16,
+    /// HTTP client shared by every request of the session
+    client: Client,
+}
+
+impl WithingsSession {
+    /// Creates a session from explicit application credentials.
+    ///
+    /// Tokens are cached in the file named by `WITHINGS_CONFIG_FILE`, or
+    /// `withings.json` in the configuration directory if it is not set, and
+    /// the public Withings URLs are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - Client ID of the Withings application
+    /// * `client_secret` - Client secret of the Withings application
+    pub fn new(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        WithingsSession {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            config_file: config::WITHINGS.env_token_path(),
+            api_url: config::WITHINGS.api_url.to_string(),
+            token_url: config::WITHINGS.token_url.to_string(),
+            auth_url: config::WITHINGS.auth_url.to_string(),
+            scope: config::WITHINGS.scope.to_string(),
+            redirect_port: config::WITHINGS.redirect_port,
+            client: Client::new(),
+        }
+    }
+
+    /// Caches the user's tokens in another file, e.g. one per profile.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the token file
+    pub fn with_token_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_file = path.into();
+        self
+    }
+
+    /// Uses the token file and URLs set in a configuration, keeping the
+    /// credentials of the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Withings configuration
+    pub fn with_config(mut self, config: &ServiceConfig) -> Self {
+        if let Some(token_file) = &config.token_file {
+            self.config_file = token_file.clone();
+        }
+        if let Some(api_url) = &config.api_url {
+            self.api_url = api_url.clone();
+        }
+        if let Some(token_url) = &config.token_url {
+            self.token_url = token_url.clone();
+        }
+        if let Some(auth_url) = &config.auth_url {
+            self.auth_url = auth_url.clone();
+        }
+        if let Some(scope) = &config.scope {
+            self.scope = scope.clone();
+        }
+        if let Some(redirect_port) = config.redirect_port {
+            self.redirect_port = redirect_port;
+        }
+        self
+    }
+
+    /// Creates a session from the `[withings]` section of a configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Withings configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WithingsError::Config` if the client ID or secret is not set.
+    pub fn from_config(config: &ServiceConfig) -> Result<Self> {
+        let missing = |setting: &str, env: &str| WithingsError::Config {
+            message: format!("Missing withings.{}", setting),
+            help: format!(
+                "Set withings.{} in the configuration file or the {} environment variable",
+                setting, env
+            ),
+        };
+
+        let client_secret = config
+            .client_secret
+            .as_ref()
+            .ok_or_else(|| missing("client_secret", config::WITHINGS.client_secret_env))
+            .into_diagnostic()
+            .wrap_err("Missing client secret")?;
+        let client_id = config
+            .client_id
+            .as_ref()
+            .ok_or_else(|| missing("client_id", config::WITHINGS.client_id_env))
+            .into_diagnostic()
+            .wrap_err("Missing client ID")?;
+
+        Ok(WithingsSession::new(client_id, client_secret).with_config(config))
+    }
+
+    /// Creates a session from the `WITHINGS_CLIENT_ID` and `WITHINGS_CLIENT_SECRET`
+    /// environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WithingsError::Config` if either environment variable is not set.
+    pub fn from_env() -> Result<Self> {
+        WithingsSession::from_config(&Config::from_env().withings)
+    }
+
+    /// Retrieves or refreshes the Withings API access token
+    ///
+    /// The cached token is reused until it is about to expire, then refreshed
+    /// once and saved. Without a config file the user has to
+    /// [authorize](WithingsSession::authorize) the application first.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `String` - The access token
+    /// * `WithingsError` - Error if token retrieval fails
+    fn get_access_token(&self) -> Result<String> {
+        let access_token = token::access_token(&self.config_file, |refresh_token| {
+            self.refresh_access_token(refresh_token)
+        })
+        .map_err(|e| WithingsError::Config {
+            message: "Failed to obtain access token".to_string(),
+            help: format!("Error: {}", e),
+        })?;
+
+        access_token.ok_or_else(|| {
+            WithingsError::NotAuthorized {
+                path: self.config_file.clone(),
+            }
+            .into()
+        })
+    }
+
+    /// Authenticates with the Withings API using OAuth2 flow
+    ///
+    /// Asks the user to approve the application in the browser, receives the
+    /// redirect on `http://localhost:<redirect_port>`, checks its `state` and
+    /// stores the resulting tokens in the token file.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - Called with the URL of the authorization page, to show it
+    ///   to the user
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WithingsError::Authentication` if the OAuth2 flow fails.
+    pub fn authorize(&self, prompt: impl FnOnce(&str)) -> Result<(), WithingsError> {
+        let authorization = oauth::Authorization {
+            service: "Withings",
+            auth_url: &self.auth_url,
+            client_id: &self.client_id,
+            scope: &self.scope,
+            port: self.redirect_port,
+            extra: &[],
+        };
+        let redirect_uri = authorization.redirect_uri();
+        let grant = authorization.run(prompt).map_err(|e| {
+            // Only a redirect that never arrives points at the setup
+            let help = matches!(e, OauthError::Listen { .. } | OauthError::Timeout).then(|| {
+                format!(
+                    "Check that the callback URL of your Withings application is {} and \
+                         that port {} is free",
+                    redirect_uri, self.redirect_port
+                )
+            });
+            WithingsError::Authentication {
+                source: e.into(),
+                help,
+            }
+        })?;
+
+        let token = self
+            .request_token(&[
+                ("grant_type", "authorization_code"),
+                ("code", &grant.code),
+                ("redirect_uri", &redirect_uri),
+            ])
+            .map_err(|e| WithingsError::Authentication {
+                source: e.into(),
+                help: Some("Check your Withings credentials and try again".to_string()),
+            })?;
+
+        token::store(&self.config_file, token).map_err(|e| WithingsError::Authentication {
+            source: e.into(),
+            help: None,
+        })?;
+
+        Ok(())
+    }
+
+    /// Opens a connection to the Withings API with the session's HTTP client
+    /// and a valid access token
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WithingsError::Config` if no access token can be obtained.
+    fn api(&self) -> Result<Api> {
+        Ok(Api::new(
+            self.client.clone(),
+            &self.api_url,
+            self.get_access_token()?,
+        ))
+    }
+
+    /// Exchanges a refresh token for a new access token and refresh token
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - The current refresh token
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WithingsError::Api` if the request fails or Withings refuses
+    /// the refresh token.
+    fn refresh_access_token(&self, refresh_token: &str) -> Result<Token, WithingsError> {
+        self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+    }
+
+    /// Requests tokens from the OAuth token endpoint
+    ///
+    /// # Arguments
+    ///
+    /// * `grant` - Grant type and the code or refresh token it exchanges
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WithingsError::Api` if the request fails or Withings refuses
+    /// the grant.
+    fn request_token(&self, grant: &[(&str, &str)]) -> Result<Token, WithingsError> {
+        let mut form = vec![
+            ("action", "requesttoken"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+        ];
+        form.extend_from_slice(grant);
+
+        let body: OauthBody = request::post_body(&self.client, &self.token_url, &form)?
+            .ok_or_else(|| WithingsError::Api {
+                message: format!("{} returned no token", self.token_url),
+                src: None,
+            })?;
+
+        Ok(Token {
+            access_token: body.access_token,
+            refresh_token: body.refresh_token,
+            expires_at: Some(Utc::now().timestamp() + body.expires_in),
+        })
+    }
+
+    /// Retrieves weight measurement for a specific date from Withings API
+    ///
+    /// # Arguments
+    ///
+    /// * `lastupdate` - Timestamp string representing the date after which to fetch measurements
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `Measurement` - The most recent decoded weight measurement, with `quantity` in kilograms
+    /// * `WeightError` - Error that occurred during retrieval
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// * Authentication fails
+    /// * API request fails
+    /// * No measurements are available
+    pub fn get_weight_by_date(&self, lastupdate: String) -> Result<Measurement, WeightError> {
+        let since = lastupdate
+            .parse()
+            .map_err(|_| WeightError::Measurement(format!("Invalid timestamp {}", lastupdate)))?;
+
+        // Groups are sorted chronologically so the last measurement is the latest
+        self.get_measure_groups(Window::UpdatedSince(since), &[MeasureType::Weight])?
+            .into_iter()
+            .flat_map(|group| group.measures)
+            .next_back()
+            .ok_or(WeightError::NoMeasurements)
+    }
+
+    /// Retrieves every body measurement type in a window, one record per weigh-in
+    ///
+    /// Fetches weight, height, fat ratio, fat mass, fat free mass, muscle mass,
+    /// bone mass, hydration, heart pulse, SpO2, temperature and blood pressure
+    /// in a single request.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Period of time to fetch measurements for
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `Vec<BodyComposition>` - One record per weigh-in in chronological order
+    /// * `WeightError` - Error that occurred during retrieval
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// * Authentication fails
+    /// * API request fails
+    pub fn get_body_composition(
+        &self,
+        window: Window,
+    ) -> Result<Vec<BodyComposition>, WeightError> {
+        let groups = self.get_measure_groups(window, &measure::BODY_MEASURE_TYPES)?;
+        Ok(BodyComposition::from_groups(&groups))
+    }
+
+    /// Retrieves every measure group of the given types in a window
+    ///
+    /// Follows the API paging until the whole window has been retrieved.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Period of time to fetch measurements for
+    /// * `types` - Measure types to fetch
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `Vec<MeasureGroup>` - The measure groups in chronological order
+    /// * `WeightError` - Error that occurred during retrieval
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// * Authentication fails
+    /// * API request fails
+    pub fn get_measure_groups(
+        &self,
+        window: Window,
+        types: &[MeasureType],
+    ) -> Result<Vec<MeasureGroup>, WeightError> {
+        let access_token = self
+            .get_access_token()
+            .map_err(|e| WeightError::Auth(e.to_string()))?;
+        let api = Api::new(self.client.clone(), &self.api_url, access_token.clone());
+
+        let mut params = MeasurementParams {
+            access_token,
+            client_id: self.client_id.clone(),
+            category: CategoryType::Measures.to_string(),
+            meastype: measure::meastypes(types),
+            start: None,
+            end: None,
+            offset: None,
+            lastupdate: None,
+        };
+        window.apply(&mut params);
+
+        measure::get_measure_groups(&api, &mut params)
+    }
+
+    /// Retrieves the daily activity summaries of a window
+    ///
+    /// Each summary holds the steps, distance, elevation, calories, intensity
+    /// and heart rate totals of one day.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Period of time to fetch summaries for
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `Vec<ActivitySummary>` - One summary per day in chronological order
+    /// * `WithingsError` - Error that occurred during retrieval
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// * Authentication fails
+    /// * API request fails
+    pub fn get_activity_summaries(&self, window: Window) -> Result<Vec<ActivitySummary>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(activity::get_activity(&api, window)?)
+    }
+
+    /// Retrieves the nightly sleep summaries of a window
+    ///
+    /// Each summary holds the duration, deep/light/REM split, wake-ups, sleep
+    /// score and heart and breathing rates of one night.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Period of time to fetch summaries for
+    /// * `with_stages` - Also fetch the sleep stage segments of every night
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `Vec<SleepSummary>` - One summary per night in chronological order
+    /// * `WithingsError` - Error that occurred during retrieval
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// * Authentication fails
+    /// * API request fails
+    pub fn get_sleep_summaries(
+        &self,
+        window: Window,
+        with_stages: bool,
+    ) -> Result<Vec<SleepSummary>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        let mut summaries = sleep::get_summaries(&api, window)?;
+        if with_stages {
+            for summary in &mut summaries {
+                let stages = sleep::get_stages(&api, summary.start, summary.end)
+                    .wrap_err_with(|| format!("Failed to get sleep stages for {}", summary.date))?;
+                summary.stages = Some(stages);
+            }
+        }
+
+        Ok(summaries)
+    }
+
+    /// Retrieves the workouts of a window
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Period of time to fetch workouts for
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing either:
+    /// * `Vec<WithingsWorkout>` - The workouts in chronological order
+    /// * `WithingsError` - Error that occurred during retrieval
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// * Authentication fails
+    /// * API request fails
+    pub fn get_workouts(&self, window: Window) -> Result<Vec<WithingsWorkout>> {
+        let api = self.api().wrap_err("Failed to obtain access token")?;
+
+        Ok(workouts::get_workouts(&api, window)?)
+    }
+}
+
+impl MeasurementSource for WithingsSession {
+    fn name(&self) -> &'static str {
+        "Withings"
+    }
+
+    fn weights(&self, since: i64) -> Result<Vec<WeightReading>> {
+        let window = Window::Between {
+            start: since,
+            end: Local::now().timestamp(),
+        };
+
+        Ok(self
+            .get_measure_groups(window, &[MeasureType::Weight])?
+            .into_iter()
+            .flat_map(|group| group.measures)
+            .filter(|m| m.measure_type == MeasureType::Weight as i64)
+            .map(|m| WeightReading {
+                taken_at: m.taken_at,
+                kg: m.quantity,
+            })
+            .collect())
+    }
+}
+
+impl WorkoutSource for WithingsSession {
+    fn name(&self) -> &'static str {
+        "Withings"
+    }
+
+    fn workouts(&self, since: i64) -> Result<Vec<Workout>> {
+        let window = Window::Between {
+            start: since,
+            end: Local::now().timestamp(),
+        };
+
+        Ok(self
+            .get_workouts(window)?
+            .iter()
+            .filter(|workout| workout.start.timestamp() >= since)
+            .map(Workout::from)
+            .collect())
+    }
+}
+
+/// Period of time covered by a Withings query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// Everything created or modified after a Unix timestamp
+    UpdatedSince(i64),
+    /// Everything taken between two Unix timestamps, inclusive
+    Between {
+        /// Start of the window
+        start: i64,
+        /// End of the window
+        end: i64,
+    },
+}
+
+impl Window {
+    /// Creates a window covering whole local days from `start` to `end`, inclusive.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use chrono::NaiveDate;
+    /// use fit_connect_rs::modules::withings::Window;
+    ///
+    /// // All of March
+    /// let march = Window::days(
+    ///     NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+    ///     NaiveDate::from_ymd_opt(2024, 3, 31).unwrap(),
+    /// );
+    /// ```
+    pub fn days(start: NaiveDate, end: NaiveDate) -> Window {
+        let midnight = |date: NaiveDate| {
+            date.and_time(NaiveTime::MIN)
+                .and_local_timezone(Local)
+                .earliest()
+                .map(|t| t.timestamp())
+                .unwrap_or_default()
+        };
+
+        Window::Between {
+            start: midnight(start),
+            end: midnight(end + Duration::days(1)) - 1,
+        }
+    }
+
+    /// Sets the window on the query of endpoints that take `YYYY-MM-DD` dates.
+    fn apply_ymd(self, query: &mut HashMap<&str, String>) {
+        let ymd = |timestamp: i64| {
+            DateTime::from_timestamp(timestamp, 0)
+                .unwrap_or_default()
+                .with_timezone(&Local)
+                .format("%Y-%m-%d")
+                .to_string()
+        };
+
+        match self {
+            Window::UpdatedSince(since) => {
+                query.insert("lastupdate", since.to_string());
+            }
+            Window::Between { start, end } => {
+                query.insert("startdateymd", ymd(start));
+                query.insert("enddateymd", ymd(end));
+            }
+        }
+    }
+
+    /// Sets the window on the measure request parameters.
+    fn apply(self, params: &mut MeasurementParams) {
+        match self {
+            Window::UpdatedSince(since) => params.lastupdate = Some(since.to_string()),
+            Window::Between { start, end } => {
+                params.start = Some(start.to_string());
+                params.end = Some(end.to_string());
+            }
+        }
+    }
+}
+
+/// Calculates a timestamp for a specified number of days before the current date
+///
+/// # Arguments
+///
+/// * `day` - Number of days to subtract from the current date
+///
+/// # Returns
+///
+/// Returns the Unix timestamp for the calculated date
+///
+/// # Examples
+///
+/// ```rust
+/// use fit_connect_rs::modules::withings::get_day_before_timestamp;
+///
+/// // Get timestamp for yesterday
+/// let yesterday = get_day_before_timestamp(1);
+/// ```
+pub fn get_day_before_timestamp(day: i64) -> i64 {
+    let current_time: DateTime<Local> = Local::now();
+    let a_day_before = current_time - Duration::days(day);
+
+    a_day_before.timestamp()
+}
@@ -0,0 +1,229 @@
+//! Request helpers shared by the Withings endpoints
+//!
+//! Every Withings response is wrapped in a `{"status": .., "body": ..}`
+//! envelope and errors are reported through a non-zero status on an HTTP 200
+//! response, so the envelope is checked here once for all endpoints.
+
+use log::{trace, warn};
+use reqwest::blocking::Client;
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use super::WithingsError;
+
+/// Envelope shared by every Withings API response
+#[derive(Deserialize)]
+struct Envelope<T> {
+    status: i64,
+    body: Option<T>,
+}
+
+/// Authenticated connection to the Withings API
+///
+/// Holds the HTTP client of the session, so that requests reuse its
+/// connections, the base URL of the API and the access token sent with every
+/// request.
+#[derive(Debug, Clone)]
+pub(crate) struct Api {
+    /// HTTP client of the session
+    client: Client,
+    /// Base URL of the API, without a trailing slash
+    base_url: String,
+    /// Access token of the user
+    access_token: String,
+}
+
+impl Api {
+    /// Creates a connection from the HTTP client of a session, the base URL
+    /// of the API and an access token.
+    pub(crate) fn new(client: Client, base_url: &str, access_token: String) -> Self {
+        Api {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            access_token,
+        }
+    }
+
+    /// Calls a Withings API endpoint and returns the response body.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the endpoint, e.g. `v2/measure`
+    /// * `query` - Query parameters including the `action`
+    ///
+    /// # Returns
+    ///
+    /// The body of the response, or `None` if the API returned no body.
+    ///
+    /// # Errors
+    ///
+    /// Returns `WithingsError::Api` if the request fails, the API reports a
+    /// non-zero status or the body cannot be parsed.
+    pub(crate) fn get_body<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &HashMap<&str, String>,
+    ) -> Result<Option<T>, WithingsError> {
+        trace!("Withings {} query parameters: {:?}", path, query);
+
+        let response = self
+            .client
+            .get(format!("{}/{}", self.base_url, path))
+            .bearer_auth(&self.access_token)
+            .query(query)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json::<Envelope<T>>())
+            .map_err(|e| WithingsError::Api {
+                message: format!("Request to {} failed", path),
+                src: Some(e.to_string()),
+            })?;
+
+        open(path, response)
+    }
+}
+
+/// Posts form parameters to a Withings API endpoint that needs no access
+/// token, such as the OAuth token endpoint, and returns the response body.
+///
+/// # Arguments
+///
+/// * `client` - HTTP client of the session
+/// * `url` - URL of the endpoint, e.g. `https://wbsapi.withings.net/v2/oauth2`
+/// * `form` - Form parameters including the `action`
+///
+/// # Returns
+///
+/// The body of the response, or `None` if the API returned no body.
+///
+/// # Errors
+///
+/// Returns `WithingsError::Api` if the request fails, the API reports a
+/// non-zero status or the body cannot be parsed.
+pub(crate) fn post_body<T: DeserializeOwned>(
+    client: &Client,
+    url: &str,
+    form: &[(&str, &str)],
+) -> Result<Option<T>, WithingsError> {
+    let response = client
+        .post(url)
+        .form(form)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.json::<Envelope<T>>())
+        .map_err(|e| WithingsError::Api {
+            message: format!("Request to {} failed", url),
+            src: Some(e.to_string()),
+        })?;
+
+    open(url, response)
+}
+
+/// Checks the status of a response envelope and returns its body.
+fn open<T>(path: &str, response: Envelope<T>) -> Result<Option<T>, WithingsError> {
+    if response.status != 0 {
+        warn!("Withings {} returned status {}", path, response.status);
+        return Err(WithingsError::Api {
+            message: format!("{} returned status {}", path, response.status),
+            src: None,
+        });
+    }
+
+    Ok(response.body)
+}
+
+/// Deserializes the `more` paging flag, which some endpoints send as a
+/// boolean and others as 0 or 1.
+pub(crate) fn deserialize_more<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum More {
+        Flag(bool),
+        Count(i64),
+    }
+
+    Ok(match Option::<More>::deserialize(deserializer)? {
+        Some(More::Flag(more)) => more,
+        Some(More::Count(count)) => count != 0,
+        None => false,
+    })
+}
+
+/// Returns the offset of the next page, or `None` once paging is over.
+///
+/// Paging also stops if the API asks for another page without moving past
+/// the offset of the previous one, the first page counting as offset 0, as
+/// that page would be requested forever. A missing offset reads as 0.
+///
+/// # Arguments
+///
+/// * `path` - Path of the endpoint, used in the warning
+/// * `more` - The `more` flag of the response
+/// * `offset` - The `offset` of the response
+/// * `previous` - The offset the response was requested with, if any
+pub(crate) fn next_offset(
+    path: &str,
+    more: bool,
+    offset: i64,
+    previous: Option<i64>,
+) -> Option<i64> {
+    if !more {
+        return None;
+    }
+    if offset <= previous.unwrap_or(0) {
+        warn!(
+            "Withings {} reported more results without advancing past offset {}",
+            path, offset
+        );
+        return None;
+    }
+
+    Some(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Paging fields of a response body
+    #[derive(Deserialize)]
+    struct Page {
+        #[serde(default, deserialize_with = "deserialize_more")]
+        more: bool,
+        #[serde(default)]
+        offset: i64,
+    }
+
+    fn next(body: &str, previous: Option<i64>) -> Option<i64> {
+        let page: Page = serde_json::from_str(body).unwrap();
+        next_offset("v2/measure", page.more, page.offset, previous)
+    }
+
+    #[test]
+    fn stops_without_more() {
+        assert_eq!(next(r#"{"more": false, "offset": 20}"#, None), None);
+        assert_eq!(next(r#"{"more": 0, "offset": 20}"#, Some(10)), None);
+        assert_eq!(next(r#"{"offset": 20}"#, None), None);
+    }
+
+    #[test]
+    fn follows_an_advancing_offset() {
+        assert_eq!(next(r#"{"more": true, "offset": 20}"#, None), Some(20));
+        assert_eq!(next(r#"{"more": 1, "offset": 40}"#, Some(20)), Some(40));
+    }
+
+    #[test]
+    fn stops_on_a_missing_offset() {
+        assert_eq!(next(r#"{"more": true}"#, None), None);
+        assert_eq!(next(r#"{"more": true}"#, Some(20)), None);
+    }
+
+    #[test]
+    fn stops_on_an_offset_that_does_not_advance() {
+        assert_eq!(next(r#"{"more": true, "offset": 20}"#, Some(20)), None);
+        assert_eq!(next(r#"{"more": true, "offset": 10}"#, Some(20)), None);
+    }
+}
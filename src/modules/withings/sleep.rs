@@ -0,0 +1,273 @@
+//! Withings sleep summaries and sleep stages
+//!
+//! Calls the sleep v2 API: `getsummary` returns one aggregated record per
+//! night and `get` returns the stage segments of a single night.
+//! Docs: https://developer.withings.com/api-reference/#tag/sleep
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::{self, Api};
+use super::{Window, WithingsError};
+
+/// Fields requested from the sleep summary API
+const SUMMARY_DATA_FIELDS: &str = "total_sleep_time,total_timeinbed,deepsleepduration,lightsleepduration,remsleepduration,wakeupcount,wakeupduration,durationtosleep,sleep_score,hr_average,hr_min,hr_max,rr_average,rr_min,rr_max";
+
+/// Sleep state of a segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepState {
+    /// Awake
+    Awake,
+    /// Light sleep
+    Light,
+    /// Deep sleep
+    Deep,
+    /// REM sleep
+    Rem,
+    /// Sleep entered manually
+    Manual,
+    /// State not reported by the device
+    Unspecified,
+}
+
+impl From<i64> for SleepState {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => SleepState::Awake,
+            1 => SleepState::Light,
+            2 => SleepState::Deep,
+            3 => SleepState::Rem,
+            4 => SleepState::Manual,
+            _ => SleepState::Unspecified,
+        }
+    }
+}
+
+/// A continuous period spent in one sleep state
+#[derive(Debug, Clone, Serialize)]
+pub struct SleepSegment {
+    /// Start of the segment
+    pub start: DateTime<Utc>,
+    /// End of the segment
+    pub end: DateTime<Utc>,
+    /// Sleep state during the segment
+    pub state: SleepState,
+}
+
+/// Sleep totals of a single night
+#[derive(Debug, Clone, Serialize)]
+pub struct SleepSummary {
+    /// Identifier of the sleep session
+    pub id: i64,
+    /// Day the night is attributed to
+    pub date: NaiveDate,
+    /// Timezone of the night
+    pub timezone: Option<String>,
+    /// Model name of the device that tracked the night, if any
+    pub model: Option<String>,
+    /// Time the user went to bed
+    pub start: DateTime<Utc>,
+    /// Time the user got up
+    pub end: DateTime<Utc>,
+    /// Total time asleep (s)
+    pub total_sleep_time: Option<i64>,
+    /// Total time in bed (s)
+    pub total_time_in_bed: Option<i64>,
+    /// Time spent in deep sleep (s)
+    pub deep_sleep_duration: Option<i64>,
+    /// Time spent in light sleep (s)
+    pub light_sleep_duration: Option<i64>,
+    /// Time spent in REM sleep (s)
+    pub rem_sleep_duration: Option<i64>,
+    /// Number of times the user woke up
+    pub wakeup_count: Option<i64>,
+    /// Time spent awake after falling asleep (s)
+    pub wakeup_duration: Option<i64>,
+    /// Time it took to fall asleep (s)
+    pub duration_to_sleep: Option<i64>,
+    /// Sleep score between 0 and 100
+    pub sleep_score: Option<i64>,
+    /// Average heart rate (bpm)
+    pub hr_average: Option<i64>,
+    /// Minimum heart rate (bpm)
+    pub hr_min: Option<i64>,
+    /// Maximum heart rate (bpm)
+    pub hr_max: Option<i64>,
+    /// Average breathing rate (breaths per minute)
+    pub rr_average: Option<i64>,
+    /// Minimum breathing rate (breaths per minute)
+    pub rr_min: Option<i64>,
+    /// Maximum breathing rate (breaths per minute)
+    pub rr_max: Option<i64>,
+    /// Sleep stage segments of the night, when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stages: Option<Vec<SleepSegment>>,
+}
+
+/// Body of the sleep summary response
+#[derive(Deserialize)]
+struct SummaryBody {
+    series: Vec<RawSummary>,
+    #[serde(default, deserialize_with = "request::deserialize_more")]
+    more: bool,
+    #[serde(default)]
+    offset: i64,
+}
+
+/// Sleep summary as returned by the API
+#[derive(Deserialize)]
+struct RawSummary {
+    id: i64,
+    date: NaiveDate,
+    timezone: Option<String>,
+    model: Option<String>,
+    startdate: i64,
+    enddate: i64,
+    data: RawSummaryData,
+}
+
+/// Sleep totals as returned by the API
+#[derive(Deserialize)]
+struct RawSummaryData {
+    total_sleep_time: Option<i64>,
+    total_timeinbed: Option<i64>,
+    deepsleepduration: Option<i64>,
+    lightsleepduration: Option<i64>,
+    remsleepduration: Option<i64>,
+    wakeupcount: Option<i64>,
+    wakeupduration: Option<i64>,
+    durationtosleep: Option<i64>,
+    sleep_score: Option<i64>,
+    hr_average: Option<i64>,
+    hr_min: Option<i64>,
+    hr_max: Option<i64>,
+    rr_average: Option<i64>,
+    rr_min: Option<i64>,
+    rr_max: Option<i64>,
+}
+
+impl From<RawSummary> for SleepSummary {
+    fn from(raw: RawSummary) -> Self {
+        let data = raw.data;
+        SleepSummary {
+            id: raw.id,
+            date: raw.date,
+            timezone: raw.timezone,
+            model: raw.model,
+            start: DateTime::from_timestamp(raw.startdate, 0).unwrap_or_default(),
+            end: DateTime::from_timestamp(raw.enddate, 0).unwrap_or_default(),
+            total_sleep_time: data.total_sleep_time,
+            total_time_in_bed: data.total_timeinbed,
+            deep_sleep_duration: data.deepsleepduration,
+            light_sleep_duration: data.lightsleepduration,
+            rem_sleep_duration: data.remsleepduration,
+            wakeup_count: data.wakeupcount,
+            wakeup_duration: data.wakeupduration,
+            duration_to_sleep: data.durationtosleep,
+            sleep_score: data.sleep_score,
+            hr_average: data.hr_average,
+            hr_min: data.hr_min,
+            hr_max: data.hr_max,
+            rr_average: data.rr_average,
+            rr_min: data.rr_min,
+            rr_max: data.rr_max,
+            stages: None,
+        }
+    }
+}
+
+/// Body of the sleep stage response
+#[derive(Deserialize)]
+struct StageBody {
+    series: Vec<RawSegment>,
+}
+
+/// Sleep stage segment as returned by the API
+#[derive(Deserialize)]
+struct RawSegment {
+    startdate: i64,
+    enddate: i64,
+    state: i64,
+}
+
+/// Retrieves the nightly sleep summaries of a window, following the API paging.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Withings API
+/// * `window` - Period of time to fetch summaries for
+///
+/// # Returns
+///
+/// One summary per night, in chronological order.
+///
+/// # Errors
+///
+/// Returns `WithingsError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn get_summaries(api: &Api, window: Window) -> Result<Vec<SleepSummary>, WithingsError> {
+    let mut query = HashMap::new();
+    query.insert("action", "getsummary".to_string());
+    query.insert("data_fields", SUMMARY_DATA_FIELDS.to_string());
+    window.apply_ymd(&mut query);
+
+    let mut summaries = Vec::new();
+    let mut previous = None;
+    while let Some(body) = api.get_body::<SummaryBody>("v2/sleep", &query)? {
+        summaries.extend(body.series.into_iter().map(SleepSummary::from));
+
+        let Some(offset) = request::next_offset("v2/sleep", body.more, body.offset, previous)
+        else {
+            break;
+        };
+        query.insert("offset", offset.to_string());
+        previous = Some(offset);
+    }
+
+    summaries.sort_by_key(|summary| summary.start);
+    Ok(summaries)
+}
+
+/// Retrieves the sleep stage segments between two times.
+///
+/// The API limits a single request to 24 hours, which covers one night.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Withings API
+/// * `start` - Start of the period
+/// * `end` - End of the period
+///
+/// # Returns
+///
+/// The segments of the period, in chronological order.
+///
+/// # Errors
+///
+/// Returns `WithingsError::Api` if the request fails or the response cannot be parsed.
+pub(crate) fn get_stages(
+    api: &Api,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<SleepSegment>, WithingsError> {
+    let mut query = HashMap::new();
+    query.insert("action", "get".to_string());
+    query.insert("startdate", start.timestamp().to_string());
+    query.insert("enddate", end.timestamp().to_string());
+
+    let mut segments: Vec<SleepSegment> = api
+        .get_body::<StageBody>("v2/sleep", &query)?
+        .map(|body| body.series)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|raw| SleepSegment {
+            start: DateTime::from_timestamp(raw.startdate, 0).unwrap_or_default(),
+            end: DateTime::from_timestamp(raw.enddate, 0).unwrap_or_default(),
+            state: SleepState::from(raw.state),
+        })
+        .collect();
+
+    segments.sort_by_key(|segment| segment.start);
+    Ok(segments)
+}
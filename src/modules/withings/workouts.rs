@@ -0,0 +1,212 @@
+//! Withings workouts
+//!
+//! Calls the `getworkouts` action of the measure v2 API, which returns the
+//! workouts detected or recorded by watches and trackers.
+//! Docs: https://developer.withings.com/api-reference/#tag/measure/operation/measurev2-getworkouts
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::{self, Api};
+use super::{Attribution, Window, WithingsError};
+use crate::modules::workout::{Sport, Workout};
+
+/// Fields requested from the workouts API
+const DATA_FIELDS: &str = "calories,intensity,manual_distance,manual_calories,hr_average,hr_min,hr_max,pause_duration,steps,distance,elevation";
+
+/// A workout recorded by a Withings device
+#[derive(Debug, Clone, Serialize)]
+pub struct WithingsWorkout {
+    /// Identifier of the workout
+    pub id: i64,
+    /// Withings workout category code, e.g. 2 for running
+    pub category: i64,
+    /// Day the workout is attributed to
+    pub date: NaiveDate,
+    /// Start of the workout
+    pub start: DateTime<Utc>,
+    /// End of the workout
+    pub end: DateTime<Utc>,
+    /// Timezone of the workout
+    pub timezone: Option<String>,
+    /// Identifier of the device that recorded the workout, if any
+    pub device_id: Option<String>,
+    /// Model name of the device that recorded the workout, if any
+    pub model: Option<String>,
+    /// How the workout was captured
+    pub attribution: Attribution,
+    /// Active calories burned (kcal)
+    pub calories: Option<f64>,
+    /// Distance covered (m)
+    pub distance: Option<f64>,
+    /// Number of steps
+    pub steps: Option<i64>,
+    /// Elevation climbed (m)
+    pub elevation: Option<f64>,
+    /// Intensity reported by the user, between 0 and 100
+    pub intensity: Option<i64>,
+    /// Time the workout was paused (s)
+    pub pause_duration: Option<i64>,
+    /// Average heart rate (bpm)
+    pub hr_average: Option<i64>,
+    /// Minimum heart rate (bpm)
+    pub hr_min: Option<i64>,
+    /// Maximum heart rate (bpm)
+    pub hr_max: Option<i64>,
+}
+
+impl WithingsWorkout {
+    /// Returns the sport of the workout category and whether it was indoors
+    pub fn sport(&self) -> (Sport, bool) {
+        match self.category {
+            1 => (Sport::Walk, false),
+            2 => (Sport::Run, false),
+            3 => (Sport::Hike, false),
+            4 | 196 => (Sport::Skate, false),
+            5 | 6 => (Sport::Ride, false),
+            7 => (Sport::Swim, false),
+            12 => (Sport::Tennis, false),
+            16 => (Sport::WeightTraining, false),
+            18 => (Sport::Elliptical, true),
+            19 => (Sport::Pilates, true),
+            21 => (Sport::Soccer, false),
+            27 => (Sport::Golf, false),
+            28 => (Sport::Yoga, true),
+            34 => (Sport::Ski, false),
+            35 => (Sport::Snowboard, false),
+            187 => (Sport::Rowing, false),
+            195 => (Sport::Climbing, false),
+            306 => (Sport::Walk, true),
+            307 => (Sport::Run, true),
+            308 => (Sport::Ride, true),
+            _ => (Sport::Workout, false),
+        }
+    }
+}
+
+impl From<&WithingsWorkout> for Workout {
+    fn from(workout: &WithingsWorkout) -> Self {
+        let (sport, indoor) = workout.sport();
+        Workout {
+            id: workout.id.to_string(),
+            sport,
+            indoor,
+            start: workout.start,
+            end: workout.end,
+            distance: workout.distance.filter(|distance| *distance > 0.0),
+            calories: workout.calories,
+            hr_average: workout.hr_average,
+            hr_max: workout.hr_max,
+        }
+    }
+}
+
+/// Body of the workouts response
+#[derive(Deserialize)]
+struct WorkoutsBody {
+    series: Vec<RawWorkout>,
+    #[serde(default, deserialize_with = "request::deserialize_more")]
+    more: bool,
+    #[serde(default)]
+    offset: i64,
+}
+
+/// Workout as returned by the API
+#[derive(Deserialize)]
+struct RawWorkout {
+    id: i64,
+    category: i64,
+    date: NaiveDate,
+    startdate: i64,
+    enddate: i64,
+    timezone: Option<String>,
+    deviceid: Option<String>,
+    model: Option<String>,
+    #[serde(default)]
+    attrib: i64,
+    data: RawWorkoutData,
+}
+
+/// Workout metrics as returned by the API
+#[derive(Deserialize)]
+struct RawWorkoutData {
+    calories: Option<f64>,
+    manual_calories: Option<f64>,
+    distance: Option<f64>,
+    manual_distance: Option<f64>,
+    steps: Option<i64>,
+    elevation: Option<f64>,
+    intensity: Option<i64>,
+    pause_duration: Option<i64>,
+    hr_average: Option<i64>,
+    hr_min: Option<i64>,
+    hr_max: Option<i64>,
+}
+
+impl From<RawWorkout> for WithingsWorkout {
+    fn from(raw: RawWorkout) -> Self {
+        let data = raw.data;
+        WithingsWorkout {
+            id: raw.id,
+            category: raw.category,
+            date: raw.date,
+            start: DateTime::from_timestamp(raw.startdate, 0).unwrap_or_default(),
+            end: DateTime::from_timestamp(raw.enddate, 0).unwrap_or_default(),
+            timezone: raw.timezone,
+            device_id: raw.deviceid,
+            model: raw.model,
+            attribution: Attribution::from(raw.attrib),
+            // Values entered by the user take precedence over the device estimate
+            calories: data.manual_calories.filter(|c| *c > 0.0).or(data.calories),
+            distance: data.manual_distance.filter(|d| *d > 0.0).or(data.distance),
+            steps: data.steps,
+            elevation: data.elevation,
+            intensity: data.intensity,
+            pause_duration: data.pause_duration,
+            hr_average: data.hr_average,
+            hr_min: data.hr_min,
+            hr_max: data.hr_max,
+        }
+    }
+}
+
+/// Retrieves the workouts of a window, following the API paging.
+///
+/// # Arguments
+///
+/// * `api` - Connection to the Withings API
+/// * `window` - Period of time to fetch workouts for
+///
+/// # Returns
+///
+/// The workouts of the window, in chronological order.
+///
+/// # Errors
+///
+/// Returns `WithingsError::Api` if a request fails or a response cannot be parsed.
+pub(crate) fn get_workouts(
+    api: &Api,
+    window: Window,
+) -> Result<Vec<WithingsWorkout>, WithingsError> {
+    let mut query = HashMap::new();
+    query.insert("action", "getworkouts".to_string());
+    query.insert("data_fields", DATA_FIELDS.to_string());
+    window.apply_ymd(&mut query);
+
+    let mut workouts = Vec::new();
+    let mut previous = None;
+    while let Some(body) = api.get_body::<WorkoutsBody>("v2/measure", &query)? {
+        workouts.extend(body.series.into_iter().map(WithingsWorkout::from));
+
+        let Some(offset) = request::next_offset("v2/measure", body.more, body.offset, previous)
+        else {
+            break;
+        };
+        query.insert("offset", offset.to_string());
+        previous = Some(offset);
+    }
+
+    workouts.sort_by_key(|workout| workout.start);
+    Ok(workouts)
+}
@@ -0,0 +1,141 @@
+//! Workouts shared between services.
+//!
+//! Every service maps its own workout or activity types to [`Sport`], so a
+//! workout recorded by one service can be recreated on another.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+
+/// Kind of sport of a workout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sport {
+    /// Walking
+    Walk,
+    /// Running
+    Run,
+    /// Hiking
+    Hike,
+    /// Cycling
+    Ride,
+    /// Swimming
+    Swim,
+    /// Rowing, indoors or on the water
+    Rowing,
+    /// Alpine or cross-country skiing
+    Ski,
+    /// Snowboarding
+    Snowboard,
+    /// Ice or inline skating
+    Skate,
+    /// Yoga
+    Yoga,
+    /// Pilates
+    Pilates,
+    /// Strength training with weights
+    WeightTraining,
+    /// Elliptical trainer
+    Elliptical,
+    /// Tennis
+    Tennis,
+    /// Golf
+    Golf,
+    /// Soccer
+    Soccer,
+    /// Rock or indoor climbing
+    Climbing,
+    /// Any sport without a more specific kind
+    Workout,
+}
+
+impl fmt::Display for Sport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Sport::Walk => "Walk",
+            Sport::Run => "Run",
+            Sport::Hike => "Hike",
+            Sport::Ride => "Ride",
+            Sport::Swim => "Swim",
+            Sport::Rowing => "Rowing",
+            Sport::Ski => "Ski",
+            Sport::Snowboard => "Snowboard",
+            Sport::Skate => "Skate",
+            Sport::Yoga => "Yoga",
+            Sport::Pilates => "Pilates",
+            Sport::WeightTraining => "Weight Training",
+            Sport::Elliptical => "Elliptical",
+            Sport::Tennis => "Tennis",
+            Sport::Golf => "Golf",
+            Sport::Soccer => "Soccer",
+            Sport::Climbing => "Climbing",
+            Sport::Workout => "Workout",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A workout recorded by a service
+#[derive(Debug, Clone, Serialize)]
+pub struct Workout {
+    /// Identifier of the workout in the service that recorded it
+    pub id: String,
+    /// Kind of sport
+    pub sport: Sport,
+    /// Whether the workout was done indoors, e.g. on a treadmill
+    pub indoor: bool,
+    /// Start of the workout
+    pub start: DateTime<Utc>,
+    /// End of the workout
+    pub end: DateTime<Utc>,
+    /// Distance covered (m)
+    pub distance: Option<f64>,
+    /// Active calories burned (kcal)
+    pub calories: Option<f64>,
+    /// Average heart rate (bpm)
+    pub hr_average: Option<i64>,
+    /// Maximum heart rate (bpm)
+    pub hr_max: Option<i64>,
+}
+
+impl Workout {
+    /// Returns the elapsed time of the workout in seconds
+    pub fn elapsed_time(&self) -> i64 {
+        (self.end - self.start).num_seconds()
+    }
+
+    /// Returns a short description of the workout metrics, e.g. `512 kcal, avg HR 140 bpm`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(calories) = self.calories {
+            parts.push(format!("{:.0} kcal", calories));
+        }
+        if let Some(hr_average) = self.hr_average {
+            parts.push(format!("avg HR {} bpm", hr_average));
+        }
+        if let Some(hr_max) = self.hr_max {
+            parts.push(format!("max HR {} bpm", hr_max));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Activity created in a sink from a workout
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedActivity {
+    /// Identifier of the activity in the sink
+    pub id: i64,
+    /// The workout the activity was created from
+    pub workout: Workout,
+}
+
+/// Outcome of syncing workouts from one service to another
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorkoutSyncReport {
+    /// Name of the service the activities were created in
+    pub sink: &'static str,
+    /// Activities created in the sink
+    pub created: Vec<CreatedActivity>,
+    /// Source workouts that already existed in the sink
+    pub skipped: Vec<Workout>,
+}
@@ -1,19 +1,33 @@
-use crate::modules::withings::{get_day_before_timestamp, get_weight_by_date};
+use crate::modules::config::Units;
+use crate::modules::provider::MeasurementSource;
+use crate::modules::weight::WeightPolicy;
+use crate::modules::withings::get_day_before_timestamp;
 use std::process::exit;
 
-/// Retrieves the weight from the previous day and formats it as a string.
+/// Retrieves the weight from a measurement source and formats it as a string.
 ///
 /// # Arguments
 ///
+/// * `source` - The service to read the weight from
 /// * `day_offset` - The days to get the weight from 1 == current day, 2 == previous
+/// * `policy` - How to pick the weight when the period has several readings
+/// * `units` - Units to show the weight in
 ///
 /// # Returns
 ///
-/// An `Option<String>` representing the weight from the previous day, converted to kilograms.
-/// Returns `None` if an error occurs during retrieval of the weight or formatting.
-pub fn get_and_format_weight(day_offset: i64) -> Option<String> {
-    match get_weight_by_date(get_day_before_timestamp(day_offset)) {
-        Ok(weight) => Some((weight / 1000.0).to_string()),
+/// A `String` with the weight for the polling period and its unit, e.g. `82.4 kg`.
+/// Exits the process if an error occurs during retrieval of the weight.
+pub fn get_and_format_weight(
+    source: &dyn MeasurementSource,
+    day_offset: i64,
+    policy: WeightPolicy,
+    units: Units,
+) -> String {
+    match source.weight(get_day_before_timestamp(day_offset), policy) {
+        Ok(weight) => {
+            let (weight, unit) = units.weight(weight);
+            format!("{:.1} {}", weight, unit)
+        }
         Err(e) => {
             eprintln!("Failed to get weight for the polling period {:?}", e);
             exit(1)